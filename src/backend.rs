@@ -0,0 +1,771 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::{self, Read},
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use log::error;
+use nix::{
+    libc,
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+
+use crate::{
+    config::{Backend, Config},
+    native::{cgroup_cpu_time_ms, cgroup_memory_kb, Cgroup, NativeBackend},
+    response::ResourceUsage,
+};
+
+/// A volume mount for a container, the host path is relative to the game directory
+pub struct Mount {
+    pub host: Cow<'static, str>,
+    pub container: Cow<'static, str>,
+}
+
+impl Mount {
+    pub const fn new(host: &'static str, container: &'static str) -> Self {
+        Mount {
+            host: Cow::Borrowed(host),
+            container: Cow::Borrowed(container),
+        }
+    }
+
+    /// Mounts a file the player submitted, next to the language's source file
+    pub fn player_file(path: &str) -> Self {
+        Mount {
+            host: Cow::Owned(path.to_owned()),
+            container: Cow::Owned(format!("/player_code/{}", path)),
+        }
+    }
+}
+
+/// Describes a sandboxed process, each backend only looks at the fields it understands
+pub struct ProcessSpec<'a> {
+    /// Working directory of the process, also the base for the mounts
+    pub game_dir: &'a str,
+    /// Docker image to start the container from
+    pub image: &'a str,
+    pub mounts: &'a [Mount],
+    /// Program and arguments for the native backend, run inside `game_dir`
+    pub command: Vec<String>,
+    /// Wall time limit in seconds
+    pub time_limit: u64,
+    /// Memory limit in megabytes
+    pub memory_limit: u64,
+    pub cpus: &'a str,
+    /// Whether stdin is connected to the process
+    pub interactive: bool,
+}
+
+/// How often the usage of a running container is read
+const USAGE_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+const CGROUP_MOUNT: &str = "/sys/fs/cgroup";
+
+enum Isolation {
+    /// The child is the docker client, its resource usage says nothing about the container
+    /// which is sampled instead
+    Container(Container),
+    /// The child is the sandboxed process itself
+    Sandbox(Option<Cgroup>),
+}
+
+/// A process started by an [`ExecutionBackend`], along with whatever the backend
+/// needs to clean up once it has exited. Killed and reaped when dropped without being
+/// waited on.
+pub struct Process {
+    child: Child,
+    started: Instant,
+    isolation: Isolation,
+    /// Lets the process be killed before it exits by itself
+    registration: Registration<'static>,
+}
+
+pub struct ProcessOutput {
+    pub output: Output,
+    /// The process was killed for going over its memory limit
+    pub oom_killed: bool,
+    pub usage: ResourceUsage,
+}
+
+impl Process {
+    /// Docker client running the container named `name`
+    pub fn container(child: Child, name: String, game_dir: &str) -> Self {
+        Process {
+            registration: processes()
+                .register(child.id(), Some(name.clone()), game_dir)
+                .kill_on_drop(),
+            child,
+            started: Instant::now(),
+            isolation: Isolation::Container(Container::start(name)),
+        }
+    }
+    /// Process sandboxed on the host, the cgroup's events tell whether it ran out of memory
+    pub fn sandboxed(child: Child, cgroup: Option<Cgroup>, game_dir: &str) -> Self {
+        Process {
+            registration: processes()
+                .register(child.id(), None, game_dir)
+                .kill_on_drop(),
+            child,
+            started: Instant::now(),
+            isolation: Isolation::Sandbox(cgroup),
+        }
+    }
+    pub fn wait_with_output(self) -> io::Result<ProcessOutput> {
+        let Process {
+            mut child,
+            started,
+            isolation,
+            registration,
+        } = self;
+
+        // stdout is normally a fifo, in case it's piped it's drained on another thread
+        // so that neither pipe can fill up and block the child
+        let stdout_reader = child.stdout.take().map(|mut pipe| {
+            std::thread::spawn(move || {
+                let mut stdout = vec![];
+                pipe.read_to_end(&mut stdout).map(|_| stdout)
+            })
+        });
+        let mut stderr = vec![];
+        if let Some(mut pipe) = child.stderr.take() {
+            pipe.read_to_end(&mut stderr)?;
+        }
+        let stdout = match stdout_reader {
+            Some(reader) => reader
+                .join()
+                .map_err(|_| io::Error::other("stdout reader panicked"))??,
+            None => vec![],
+        };
+        wait_exited(child.id())?;
+        let (status, rusage) = registration.reap()?;
+        let output = Output {
+            status,
+            stdout,
+            stderr,
+        };
+        let wall_time_ms = started.elapsed().as_millis() as u64;
+
+        let (oom_killed, usage) = match isolation {
+            Isolation::Container(mut container) => {
+                let sampled = container.usage();
+                (
+                    container.oom_killed(),
+                    ResourceUsage {
+                        wall_time_ms,
+                        cpu_time_ms: sampled.cpu_time_ms,
+                        peak_memory_kb: sampled.peak_memory_kb,
+                    },
+                )
+            }
+            Isolation::Sandbox(cgroup) => {
+                let cpu_time_ms = cgroup
+                    .as_ref()
+                    .and_then(|cgroup| cgroup.cpu_time_ms())
+                    .unwrap_or_else(|| {
+                        (timeval_ms(rusage.ru_utime) + timeval_ms(rusage.ru_stime)) as u64
+                    });
+                let peak_memory_kb = cgroup
+                    .as_ref()
+                    .and_then(|cgroup| cgroup.peak_memory_kb())
+                    .unwrap_or(rusage.ru_maxrss as u64);
+                (
+                    cgroup.map(|cgroup| cgroup.oom_killed()).unwrap_or(false),
+                    ResourceUsage {
+                        wall_time_ms,
+                        cpu_time_ms: Some(cpu_time_ms),
+                        peak_memory_kb: Some(peak_memory_kb),
+                    },
+                )
+            }
+        };
+        Ok(ProcessOutput {
+            output,
+            oom_killed,
+            usage,
+        })
+    }
+}
+
+/// Container left behind by `docker run`, so that its state can be inspected once the
+/// client exited. Removed when dropped.
+struct Container {
+    name: String,
+    /// Tells the sampler to stop
+    done: Arc<AtomicBool>,
+    sampler: Option<JoinHandle<ContainerUsage>>,
+}
+
+#[derive(Default)]
+struct ContainerUsage {
+    cpu_time_ms: Option<u64>,
+    peak_memory_kb: Option<u64>,
+}
+
+impl Container {
+    fn start(name: String) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        let sampler = {
+            let name = name.clone();
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || sample_usage(&name, &done))
+        };
+        Container {
+            name,
+            done,
+            sampler: Some(sampler),
+        }
+    }
+
+    /// Last usage sampled before the container exited, only called once it did
+    fn usage(&mut self) -> ContainerUsage {
+        self.done.store(true, Ordering::Relaxed);
+        self.sampler
+            .take()
+            .and_then(|sampler| sampler.join().ok())
+            .unwrap_or_default()
+    }
+
+    /// The exit code of the client can't tell, 137 is also what a `docker kill` gives
+    fn oom_killed(&self) -> bool {
+        match Command::new("docker")
+            .args(["inspect", "--format", "{{.State.OOMKilled}}", &self.name])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+        {
+            Ok(output) if output.status.success() => {
+                parse_oom_killed(&String::from_utf8_lossy(&output.stdout))
+            }
+            Ok(_) => false,
+            Err(e) => {
+                error!("Failed to inspect container {}: {}", self.name, e);
+                false
+            }
+        }
+    }
+}
+
+fn parse_oom_killed(state: &str) -> bool {
+    state.trim() == "true"
+}
+
+/// docker removes the cgroup of a container as soon as it exits, so its usage is read
+/// while it runs. The cpu time used since the last sample is missed. Nothing is known
+/// without cgroup v2, or when the driver can't see the container's processes.
+fn sample_usage(name: &str, done: &AtomicBool) -> ContainerUsage {
+    let mut usage = ContainerUsage::default();
+    let mut cgroup = None;
+    while !done.load(Ordering::Relaxed) {
+        match &cgroup {
+            // the container might not have been created yet
+            None => cgroup = container_cgroup(name),
+            Some(path) => {
+                let cpu_time_ms = cgroup_cpu_time_ms(path);
+                if cpu_time_ms.is_none() {
+                    break;
+                }
+                usage.cpu_time_ms = cpu_time_ms;
+                let memory = cgroup_memory_kb(path, "memory.peak")
+                    .or_else(|| cgroup_memory_kb(path, "memory.current"));
+                usage.peak_memory_kb = usage.peak_memory_kb.max(memory);
+            }
+        }
+        std::thread::sleep(USAGE_SAMPLE_INTERVAL);
+    }
+    usage
+}
+
+/// cgroup of the container's main process, `None` until it started
+fn container_cgroup(name: &str) -> Option<PathBuf> {
+    let output = Command::new("docker")
+        .args(["inspect", "--format", "{{.State.Pid}}", name])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let pid = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|&pid| pid != 0)?;
+    parse_cgroup_path(&std::fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?)
+}
+
+/// Reads `/proc/<pid>/cgroup`, only the single hierarchy of cgroup v2 is understood
+fn parse_cgroup_path(cgroups: &str) -> Option<PathBuf> {
+    cgroups
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| Path::new(CGROUP_MOUNT).join(path.trim_start_matches('/')))
+}
+
+impl Drop for Container {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        // forced, the container outlives the client when the client is killed
+        if let Err(e) = Command::new("docker")
+            .args(["rm", "--force", &self.name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+        {
+            error!("Failed to remove container {}: {}", self.name, e);
+        }
+    }
+}
+
+/// Every running process started by a backend, so that games can be stopped before their
+/// processes exit by themselves
+pub struct ProcessRegistry {
+    state: Mutex<RegistryState>,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    /// Container name and game directory of every process
+    processes: HashMap<u32, (Option<String>, PathBuf)>,
+    /// Processes started in these directories are killed right away
+    killed_dirs: Vec<PathBuf>,
+    /// Every process started is killed right away
+    killed_all: bool,
+}
+
+impl RegistryState {
+    fn is_killed(&self, game_dir: &Path) -> bool {
+        self.killed_all || self.killed_dirs.iter().any(|dir| game_dir.starts_with(dir))
+    }
+}
+
+static PROCESSES: OnceLock<ProcessRegistry> = OnceLock::new();
+
+/// The processes started by this driver
+pub fn processes() -> &'static ProcessRegistry {
+    PROCESSES.get_or_init(ProcessRegistry::new)
+}
+
+impl ProcessRegistry {
+    fn new() -> Self {
+        ProcessRegistry {
+            state: Mutex::new(RegistryState::default()),
+        }
+    }
+
+    /// Tracks the process until the returned handle is dropped
+    pub fn register(
+        &self,
+        pid: u32,
+        container: Option<String>,
+        game_dir: &str,
+    ) -> Registration<'_> {
+        let game_dir = PathBuf::from(game_dir);
+        let mut state = self.state.lock().unwrap();
+        let killed = state.is_killed(&game_dir);
+        state.processes.insert(pid, (container.clone(), game_dir));
+        if killed {
+            signal_process(pid);
+        }
+        drop(state);
+        if let (true, Some(container)) = (killed, container) {
+            kill_container(&container);
+        }
+        Registration {
+            registry: self,
+            pid,
+            kill_on_drop: false,
+        }
+    }
+
+    /// Kills every process, including the ones started afterwards
+    pub fn kill_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.killed_all = true;
+        // signalled while locked, a process can't be reaped and its pid reused meanwhile
+        let containers = state
+            .processes
+            .iter()
+            .filter_map(|(pid, (container, _))| {
+                signal_process(*pid);
+                container.clone()
+            })
+            .collect::<Vec<_>>();
+        drop(state);
+        for container in containers {
+            kill_container(&container);
+        }
+    }
+
+    /// Kills every process started in `game_dir` or below it, including the ones started
+    /// afterwards until [`ProcessRegistry::release`] is called
+    pub fn kill_in(&self, game_dir: &str) {
+        let game_dir = PathBuf::from(game_dir);
+        let mut state = self.state.lock().unwrap();
+        let containers = state
+            .processes
+            .iter()
+            .filter(|(_, (_, dir))| dir.starts_with(&game_dir))
+            .filter_map(|(pid, (container, _))| {
+                signal_process(*pid);
+                container.clone()
+            })
+            .collect::<Vec<_>>();
+        state.killed_dirs.push(game_dir);
+        drop(state);
+        for container in containers {
+            kill_container(&container);
+        }
+    }
+
+    /// Processes started in `game_dir` are left alone again
+    pub fn release(&self, game_dir: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .killed_dirs
+            .retain(|dir| dir != Path::new(game_dir));
+    }
+}
+
+/// Handle of a process tracked by a [`ProcessRegistry`], which stops tracking it once dropped
+pub struct Registration<'a> {
+    registry: &'a ProcessRegistry,
+    pid: u32,
+    /// The process is killed and reaped when the handle is dropped before it was reaped
+    kill_on_drop: bool,
+}
+
+impl Registration<'_> {
+    fn kill_on_drop(mut self) -> Self {
+        self.kill_on_drop = true;
+        self
+    }
+
+    /// Stops tracking the process and reaps it with the registry locked, so that its pid
+    /// can't be signalled once it is reused. The process must have exited already.
+    fn reap(mut self) -> io::Result<(ExitStatus, libc::rusage)> {
+        let mut state = self.registry.state.lock().unwrap();
+        state.processes.remove(&self.pid);
+        self.kill_on_drop = false;
+        wait4(self.pid)
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let mut state = self.registry.state.lock().unwrap();
+        state.processes.remove(&self.pid);
+        // left behind after an early error, killed and reaped while locked like in reap
+        if self.kill_on_drop {
+            signal_process(self.pid);
+            if let Err(e) = wait4(self.pid) {
+                error!("Failed to reap process {}: {}", self.pid, e);
+            }
+        }
+    }
+}
+
+/// The docker client going away doesn't stop the container, it's killed separately
+fn kill_container(container: &str) {
+    if let Err(e) = Command::new("docker")
+        .args(["kill", container])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        error!("Failed to kill container {}: {}", container, e);
+    }
+}
+
+/// Only called with the registry locked, while the process can't have been reaped
+fn signal_process(pid: u32) {
+    // the timeout wrapper leads a process group with everything it started, it might not
+    // have created it yet though
+    let pid = Pid::from_raw(pid as i32);
+    let _ = signal::killpg(pid, Signal::SIGKILL);
+    let _ = signal::kill(pid, Signal::SIGKILL);
+}
+
+/// Waits for the child to exit without reaping it, its pid stays taken until then
+fn wait_exited(pid: u32) -> io::Result<()> {
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if ret == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Reaps the child, along with the resources used by it and its reaped descendants
+fn wait4(pid: u32) -> io::Result<(ExitStatus, libc::rusage)> {
+    let mut status = 0;
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let ret = unsafe { libc::wait4(pid as libc::pid_t, &mut status, 0, &mut rusage) };
+        if ret >= 0 {
+            return Ok((ExitStatus::from_raw(status), rusage));
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+fn timeval_ms(time: libc::timeval) -> i64 {
+    time.tv_sec * 1000 + time.tv_usec / 1000
+}
+
+pub trait ExecutionBackend: Send + Sync {
+    /// Starts the process with stderr piped, so that logs can be collected
+    fn spawn(&self, spec: &ProcessSpec, stdin: Stdio, stdout: Stdio) -> io::Result<Process>;
+
+    /// Identifies the toolchain a process would be started with, so that compiled code
+    /// isn't reused across compiler versions. `None` if it can't be told.
+    fn toolchain_id(&self, image: &str, command: &[String]) -> Option<String>;
+}
+
+pub fn from_config(config: &Config) -> Box<dyn ExecutionBackend> {
+    match config.backend {
+        Backend::Docker => Box::new(DockerBackend),
+        Backend::Native => Box::new(NativeBackend::new(config.native.clone())),
+    }
+}
+
+/// Runs every process in its own throwaway container
+pub struct DockerBackend;
+
+/// Makes the names of the containers started by this driver unique
+static NEXT_CONTAINER_ID: AtomicU64 = AtomicU64::new(0);
+
+impl ExecutionBackend for DockerBackend {
+    fn spawn(&self, spec: &ProcessSpec, stdin: Stdio, stdout: Stdio) -> io::Result<Process> {
+        let name = format!(
+            "codecharacter-{}-{}",
+            std::process::id(),
+            NEXT_CONTAINER_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        docker_command(spec, &name)
+            .current_dir(spec.game_dir)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(Stdio::piped())
+            .spawn()
+            .map(|child| Process::container(child, name, spec.game_dir))
+    }
+
+    /// Id of the local image, which changes whenever a new version of it is pulled
+    fn toolchain_id(&self, image: &str, _command: &[String]) -> Option<String> {
+        let output = Command::new("docker")
+            .args(["image", "inspect", "--format", "{{.Id}}", image])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout)
+            .ok()
+            .map(|id| id.trim().to_owned())
+            .filter(|id| !id.is_empty())
+    }
+}
+
+/// Builds `timeout --signal=KILL <time> docker run ...` with the limits and mounts of the spec,
+/// the container is named `name` so that it can be killed and inspected
+pub fn docker_command(spec: &ProcessSpec, name: &str) -> Command {
+    let mut command = Command::new("timeout");
+    command.args([
+        "--signal=KILL",
+        &spec.time_limit.to_string(),
+        "docker",
+        "run",
+        &format!("--memory={}m", spec.memory_limit),
+        &format!("--memory-swap={}m", spec.memory_limit),
+        &format!("--cpus={}", spec.cpus),
+        "--name",
+        name,
+    ]);
+    if spec.interactive {
+        command.arg("-i");
+    }
+    for mount in spec.mounts {
+        command.arg("-v").arg(format!(
+            "{}/{}:{}",
+            spec.game_dir, mount.host, mount.container
+        ));
+    }
+    command.arg(spec.image);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::process::ExitStatusExt,
+        path::PathBuf,
+        process::{Child, Command, Stdio},
+    };
+
+    use nix::{errno::Errno, sys::signal, unistd::Pid};
+
+    use super::{
+        docker_command, parse_cgroup_path, parse_oom_killed, processes, wait_exited, Mount,
+        Process, ProcessRegistry, ProcessSpec,
+    };
+
+    fn sleep() -> Child {
+        Command::new("sleep")
+            .arg("30")
+            .stdin(Stdio::null())
+            .spawn()
+            .unwrap()
+    }
+
+    #[test]
+    fn docker_command_args() {
+        let command = docker_command(
+            &ProcessSpec {
+                game_dir: "/tmp/game",
+                image: "image:latest",
+                mounts: &[Mount::new("run.py", "/player_code/run.py")],
+                command: vec![],
+                time_limit: 10,
+                memory_limit: 100,
+                cpus: "1",
+                interactive: true,
+            },
+            "codecharacter-1-0",
+        );
+        let args = command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(command.get_program(), "timeout");
+        assert_eq!(
+            args,
+            vec![
+                "--signal=KILL",
+                "10",
+                "docker",
+                "run",
+                "--memory=100m",
+                "--memory-swap=100m",
+                "--cpus=1",
+                "--name",
+                "codecharacter-1-0",
+                "-i",
+                "-v",
+                "/tmp/game/run.py:/player_code/run.py",
+                "image:latest",
+            ]
+        );
+    }
+
+    #[test]
+    fn container_cgroup_is_read_from_proc() {
+        assert_eq!(
+            parse_cgroup_path("0::/system.slice/docker-0fa0.scope\n"),
+            Some(PathBuf::from(
+                "/sys/fs/cgroup/system.slice/docker-0fa0.scope"
+            ))
+        );
+        // cgroup v1
+        assert_eq!(parse_cgroup_path("4:memory:/docker/0fa0\n"), None);
+    }
+
+    #[test]
+    fn oom_kills_are_read_from_the_container_state() {
+        assert!(parse_oom_killed("true\n"));
+        assert!(!parse_oom_killed("false\n"));
+        assert!(!parse_oom_killed(""));
+    }
+
+    #[test]
+    fn registered_processes_are_killed() {
+        let registry = ProcessRegistry::new();
+        let mut cancelled = sleep();
+        let mut other = sleep();
+        let mut finished = sleep();
+        let _cancelled = registry.register(cancelled.id(), None, "/tmp/game-1/player1");
+        let _other = registry.register(other.id(), None, "/tmp/game-10");
+        drop(registry.register(finished.id(), None, "/tmp/game-1"));
+
+        registry.kill_in("/tmp/game-1");
+        assert_eq!(cancelled.wait().unwrap().signal(), Some(9));
+        assert!(finished.try_wait().unwrap().is_none());
+        let mut late = sleep();
+        let _late = registry.register(late.id(), None, "/tmp/game-1");
+        assert_eq!(late.wait().unwrap().signal(), Some(9));
+
+        registry.release("/tmp/game-1");
+        let mut restarted = sleep();
+        let _restarted = registry.register(restarted.id(), None, "/tmp/game-1");
+        assert!(restarted.try_wait().unwrap().is_none());
+        assert!(other.try_wait().unwrap().is_none());
+
+        registry.kill_all();
+        assert_eq!(other.wait().unwrap().signal(), Some(9));
+        assert_eq!(restarted.wait().unwrap().signal(), Some(9));
+        let mut late = sleep();
+        let _late = registry.register(late.id(), None, "/tmp/game-2");
+        assert_eq!(late.wait().unwrap().signal(), Some(9));
+
+        finished.kill().unwrap();
+        finished.wait().unwrap();
+    }
+
+    #[test]
+    fn dropped_processes_are_killed_and_reaped() {
+        let child = sleep();
+        let pid = child.id();
+        drop(Process::sandboxed(child, None, "/tmp/game-4"));
+        assert!(!processes()
+            .state
+            .lock()
+            .unwrap()
+            .processes
+            .contains_key(&pid));
+        // a zombie could still be signalled
+        assert_eq!(
+            signal::kill(Pid::from_raw(pid as i32), None),
+            Err(Errno::ESRCH)
+        );
+    }
+
+    #[test]
+    fn processes_are_tracked_until_reaped() {
+        let registry = ProcessRegistry::new();
+        // reaped through the registration rather than the child
+        let pid = Command::new("true").spawn().unwrap().id();
+        let registration = registry.register(pid, None, "/tmp/game-3");
+        wait_exited(pid).unwrap();
+        // exited but not reaped, the pid can't have been reused yet
+        assert!(registry.state.lock().unwrap().processes.contains_key(&pid));
+
+        let (status, _) = registration.reap().unwrap();
+        assert!(status.success());
+        assert!(registry.state.lock().unwrap().processes.is_empty());
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use log::{info, warn};
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    backend::ExecutionBackend,
+    config::{Backend, CacheConfig, Limits},
+    error::SimulatorError,
+    response::ResourceUsage,
+    runner::LanguageRunner,
+};
+
+/// Content addressed cache of compiled player code.
+///
+/// Every entry is a directory named after the key, holding the runner's artifacts as
+/// they were laid out in the game directory.
+pub struct ArtifactCache {
+    dir: PathBuf,
+    /// In bytes
+    max_size: u64,
+    native: bool,
+    index: Mutex<Index>,
+    next_staging_id: AtomicU64,
+}
+
+struct Index {
+    /// Size of every entry in bytes
+    entries: LruCache<String, u64>,
+    total_size: u64,
+}
+
+impl Index {
+    fn evict(&mut self, dir: &Path, max_size: u64) {
+        while self.total_size > max_size {
+            let (key, size) = match self.entries.pop_lru() {
+                Some(entry) => entry,
+                None => break,
+            };
+            self.total_size -= size;
+            if let Err(e) = fs::remove_dir_all(dir.join(&key)) {
+                warn!("Failed to evict cached artifacts {}: {}", key, e);
+            }
+        }
+    }
+}
+
+impl ArtifactCache {
+    /// Opens the cache directory, entries left by a previous run are kept in the order
+    /// they were last used
+    pub fn new(config: &CacheConfig, backend: Backend) -> io::Result<Self> {
+        let dir = PathBuf::from(&config.dir);
+        fs::create_dir_all(&dir)?;
+
+        let mut existing = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let key = match entry.file_name().into_string() {
+                Ok(key) if !key.contains('.') => key,
+                // leftovers from a store that was interrupted
+                _ => {
+                    let _ = fs::remove_dir_all(entry.path());
+                    continue;
+                }
+            };
+            let last_used = entry.metadata()?.modified()?;
+            existing.push((last_used, key, path_size(&entry.path())?));
+        }
+        existing.sort();
+
+        let mut index = Index {
+            entries: LruCache::unbounded(),
+            total_size: 0,
+        };
+        for (_, key, size) in existing {
+            index.entries.put(key, size);
+            index.total_size += size;
+        }
+        let max_size = config.max_size * 1024 * 1024;
+        index.evict(&dir, max_size);
+
+        Ok(ArtifactCache {
+            dir,
+            max_size,
+            native: backend == Backend::Native,
+            index: Mutex::new(index),
+            next_staging_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Compiles the player's code, unless the artifacts of the same code compiled by the
+    /// same toolchain are cached. No resource usage is reported on a cache hit.
+    pub fn compile(
+        &self,
+        runner: &dyn LanguageRunner,
+        backend: &dyn ExecutionBackend,
+        game_dir: &str,
+        limits: &Limits,
+        files: &[String],
+    ) -> Result<Option<ResourceUsage>, SimulatorError> {
+        let artifacts = if self.native {
+            runner.native_artifacts()
+        } else {
+            runner.artifacts()
+        };
+        let key = match runner.compiler_image() {
+            Some(image) if !artifacts.is_empty() => backend
+                .toolchain_id(image, &runner.native_compile_command())
+                .and_then(|toolchain| {
+                    cache_key(runner.name(), &toolchain, Path::new(game_dir)).ok()
+                }),
+            _ => None,
+        };
+        let key = match key {
+            Some(key) => key,
+            None => return runner.compile(backend, game_dir, limits, files),
+        };
+
+        if self.restore(&key, Path::new(game_dir), &artifacts) {
+            info!("Compilation cache hit for {} ({})", game_dir, &key[..12]);
+            return Ok(None);
+        }
+        let usage = runner.compile(backend, game_dir, limits, files)?;
+        if let Err(e) = self.store(&key, Path::new(game_dir), &artifacts) {
+            warn!("Failed to cache the compiled code of {}: {}", game_dir, e);
+        }
+        Ok(usage)
+    }
+
+    /// Copies the cached artifacts into the game directory, returns whether they were found
+    fn restore(&self, key: &str, game_dir: &Path, artifacts: &[&str]) -> bool {
+        if self.index.lock().unwrap().entries.get(key).is_none() {
+            return false;
+        }
+        let entry = self.dir.join(key);
+        for artifact in artifacts {
+            if let Err(e) = copy_path(&entry.join(artifact), &game_dir.join(artifact)) {
+                // most likely evicted in the meantime
+                warn!("Failed to restore cached artifacts {}: {}", key, e);
+                return false;
+            }
+        }
+        // the modification time orders the entries when the driver is restarted
+        let _ = fs::File::open(&entry).and_then(|dir| dir.set_modified(SystemTime::now()));
+        true
+    }
+
+    fn store(&self, key: &str, game_dir: &Path, artifacts: &[&str]) -> io::Result<()> {
+        // staged under another name so that a partial entry is never visible
+        let staging = self.dir.join(format!(
+            "{}.{}",
+            key,
+            self.next_staging_id.fetch_add(1, Ordering::Relaxed)
+        ));
+        let staged = fs::create_dir(&staging)
+            .and_then(|_| {
+                artifacts.iter().try_for_each(|artifact| {
+                    copy_path(&game_dir.join(artifact), &staging.join(artifact))
+                })
+            })
+            .and_then(|_| path_size(&staging));
+        let size = match staged {
+            Ok(size) => size,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(e);
+            }
+        };
+
+        let mut index = self.index.lock().unwrap();
+        if let Err(e) = fs::rename(&staging, self.dir.join(key)) {
+            let _ = fs::remove_dir_all(&staging);
+            // stored by another game with the same code in the meantime
+            return if index.entries.contains(key) {
+                Ok(())
+            } else {
+                Err(e)
+            };
+        }
+        index.entries.put(key.to_owned(), size);
+        index.total_size += size;
+        index.evict(&self.dir, self.max_size);
+        Ok(())
+    }
+}
+
+/// Hash of the language, the toolchain and every file in the game directory before
+/// compilation, which covers both the player's code and the boilerplate
+fn cache_key(language: &str, toolchain: &str, game_dir: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    for part in [language, toolchain] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+
+    let mut files = vec![];
+    list_files(game_dir, game_dir, &mut files)?;
+    files.sort();
+    for file in files {
+        let contents = fs::read(game_dir.join(&file))?;
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Paths of the files under `dir`, relative to `root`
+fn list_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_files(root, &path, files)?;
+        } else {
+            files.push(path.strip_prefix(root).unwrap().to_owned());
+        }
+    }
+    Ok(())
+}
+
+fn path_size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += path_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Symlinks aren't followed but refused, like anything else that isn't a regular file or a
+/// directory. The artifacts are left by the compile step, which runs the player's code.
+fn copy_path(src: &Path, dst: &Path) -> io::Result<()> {
+    let file_type = fs::symlink_metadata(src)?.file_type();
+    if file_type.is_dir() {
+        fs::create_dir_all(dst)?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else if file_type.is_file() {
+        fs::copy(src, dst).map(|_| ())
+    } else {
+        Err(io::Error::other(format!(
+            "{} is neither a file nor a directory",
+            src.display()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::symlink, path::Path};
+
+    use super::{cache_key, ArtifactCache};
+    use crate::config::{Backend, CacheConfig};
+
+    fn fresh_dir(name: &str) -> String {
+        let dir = format!("/tmp/cc-driver-{}", name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn key_depends_on_code_language_and_toolchain() {
+        let game_dir = fresh_dir("cache-key");
+        let game_dir = Path::new(&game_dir);
+        fs::write(game_dir.join("run.cpp"), "int main() {}").unwrap();
+        fs::create_dir(game_dir.join("run")).unwrap();
+        fs::write(game_dir.join("run/player.hpp"), "#pragma once").unwrap();
+
+        let key = cache_key("C++", "sha256:1", game_dir).unwrap();
+        assert_eq!(key, cache_key("C++", "sha256:1", game_dir).unwrap());
+        assert_ne!(key, cache_key("C++", "sha256:2", game_dir).unwrap());
+        assert_ne!(key, cache_key("java", "sha256:1", game_dir).unwrap());
+
+        fs::write(game_dir.join("run.cpp"), "int main() { return 1; }").unwrap();
+        assert_ne!(key, cache_key("C++", "sha256:1", game_dir).unwrap());
+
+        fs::remove_dir_all(game_dir).unwrap();
+    }
+
+    #[test]
+    fn artifacts_are_restored_and_evicted() {
+        let cache = ArtifactCache::new(
+            &CacheConfig {
+                enabled: true,
+                dir: fresh_dir("cache-entries"),
+                max_size: 1,
+            },
+            Backend::Docker,
+        )
+        .unwrap();
+        let game_dir = fresh_dir("cache-game");
+        let game_dir = Path::new(&game_dir);
+        fs::create_dir(game_dir.join("run")).unwrap();
+
+        fs::write(game_dir.join("run/player"), vec![1; 600 * 1024]).unwrap();
+        cache.store("first", game_dir, &["run"]).unwrap();
+        fs::write(game_dir.join("run/player"), vec![2; 600 * 1024]).unwrap();
+        cache.store("second", game_dir, &["run"]).unwrap();
+
+        fs::remove_dir_all(game_dir.join("run")).unwrap();
+        assert!(!cache.restore("first", game_dir, &["run"]));
+        assert!(cache.restore("second", game_dir, &["run"]));
+        assert_eq!(
+            fs::read(game_dir.join("run/player")).unwrap(),
+            vec![2; 600 * 1024]
+        );
+
+        fs::remove_dir_all(game_dir).unwrap();
+        fs::remove_dir_all("/tmp/cc-driver-cache-entries").unwrap();
+    }
+
+    #[test]
+    fn symlinked_artifacts_are_not_cached() {
+        let cache = ArtifactCache::new(
+            &CacheConfig {
+                enabled: true,
+                dir: fresh_dir("cache-symlinks"),
+                max_size: 1,
+            },
+            Backend::Docker,
+        )
+        .unwrap();
+        let game_dir = fresh_dir("cache-symlinks-game");
+        let game_dir = Path::new(&game_dir);
+        fs::write(game_dir.join("secret"), "host file").unwrap();
+
+        symlink(game_dir.join("secret"), game_dir.join("player")).unwrap();
+        assert!(cache.store("top", game_dir, &["player"]).is_err());
+        fs::create_dir(game_dir.join("run")).unwrap();
+        fs::write(game_dir.join("run/player"), "compiled").unwrap();
+        symlink(game_dir.join("secret"), game_dir.join("run/data")).unwrap();
+        assert!(cache.store("nested", game_dir, &["run"]).is_err());
+
+        assert!(!cache.restore("top", game_dir, &["player"]));
+        assert!(!cache.restore("nested", game_dir, &["run"]));
+        assert_eq!(
+            fs::read_dir("/tmp/cc-driver-cache-symlinks")
+                .unwrap()
+                .count(),
+            0
+        );
+
+        fs::remove_dir_all(game_dir).unwrap();
+        fs::remove_dir_all("/tmp/cc-driver-cache-symlinks").unwrap();
+    }
+}
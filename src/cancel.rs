@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use log::info;
+
+use crate::backend::processes;
+
+/// Games queued or running on this driver, so that they can be cancelled
+pub struct Cancellations {
+    games: Mutex<HashMap<String, TrackedGame>>,
+}
+
+#[derive(Default)]
+struct TrackedGame {
+    /// Requests with the game's id that aren't done yet
+    jobs: usize,
+    cancelled: bool,
+    /// Directories the game's processes are started in
+    dirs: Vec<String>,
+}
+
+static CANCELLATIONS: OnceLock<Cancellations> = OnceLock::new();
+
+/// The games that can be cancelled on this driver
+pub fn cancellations() -> &'static Cancellations {
+    CANCELLATIONS.get_or_init(Cancellations::new)
+}
+
+impl Cancellations {
+    fn new() -> Self {
+        Cancellations {
+            games: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A request for the game was queued
+    pub fn track(&self, game_id: &str) {
+        self.games
+            .lock()
+            .unwrap()
+            .entry(game_id.to_owned())
+            .or_default()
+            .jobs += 1;
+    }
+
+    /// A request for the game is done, it is forgotten once all of them are
+    pub fn untrack(&self, game_id: &str) {
+        let mut games = self.games.lock().unwrap();
+        let game = match games.get_mut(game_id) {
+            Some(game) => game,
+            None => return,
+        };
+        game.jobs -= 1;
+        if game.jobs == 0 {
+            if let Some(game) = games.remove(game_id) {
+                if game.cancelled {
+                    game.dirs.iter().for_each(|dir| processes().release(dir));
+                }
+            }
+        }
+    }
+
+    /// The game's processes are started in `dir`, they're killed if it is cancelled
+    pub fn started_in(&self, game_id: &str, dir: &str) {
+        let mut games = self.games.lock().unwrap();
+        if let Some(game) = games.get_mut(game_id) {
+            game.dirs.push(dir.to_owned());
+            if game.cancelled {
+                processes().kill_in(dir);
+            }
+        }
+    }
+
+    /// Kills the game's processes and marks it as cancelled, returns whether it was queued
+    /// or running
+    pub fn cancel(&self, game_id: &str) -> bool {
+        let mut games = self.games.lock().unwrap();
+        let game = match games.get_mut(game_id) {
+            Some(game) => game,
+            None => return false,
+        };
+        if !game.cancelled {
+            info!("Cancelling game {}", game_id);
+            game.cancelled = true;
+            game.dirs.iter().for_each(|dir| processes().kill_in(dir));
+        }
+        true
+    }
+
+    pub fn is_cancelled(&self, game_id: &str) -> bool {
+        self.games
+            .lock()
+            .unwrap()
+            .get(game_id)
+            .map(|game| game.cancelled)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::process::ExitStatusExt,
+        process::{Command, Stdio},
+    };
+
+    use super::Cancellations;
+    use crate::backend::processes;
+
+    #[test]
+    fn cancelled_games_are_killed() {
+        let cancellations = Cancellations::new();
+        assert!(!cancellations.cancel("cancel-test"));
+
+        cancellations.track("cancel-test");
+        cancellations.started_in("cancel-test", "/tmp/cc-driver-cancel-test");
+        let mut player = Command::new("sleep")
+            .arg("30")
+            .stdin(Stdio::null())
+            .spawn()
+            .unwrap();
+        let _registration =
+            processes().register(player.id(), None, "/tmp/cc-driver-cancel-test/player1");
+        assert!(!cancellations.is_cancelled("cancel-test"));
+
+        assert!(cancellations.cancel("cancel-test"));
+        assert!(cancellations.is_cancelled("cancel-test"));
+        assert_eq!(player.wait().unwrap().signal(), Some(9));
+
+        cancellations.untrack("cancel-test");
+        assert!(!cancellations.is_cancelled("cancel-test"));
+        assert!(!cancellations.cancel("cancel-test"));
+    }
+}
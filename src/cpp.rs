@@ -1,75 +1,60 @@
-use std::{
-    fs::File,
-    process::{Child, Command, Stdio},
-};
-
-use crate::{
-    error::SimulatorError, handle_process, COMPILATION_MEMORY_LIMIT, COMPILATION_TIME_LIMIT,
-    RUNTIME_MEMORY_LIMIT, RUNTIME_TIME_LIMIT,
-};
+use crate::{backend::Mount, runner::LanguageRunner};
 
 pub struct Runner {
-    current_dir: String,
+    compiler_image: String,
+    runner_image: String,
 }
+
 impl Runner {
-    pub fn new(current_dir: String) -> Self {
-        Runner { current_dir }
+    pub fn new(compiler_image: String, runner_image: String) -> Self {
+        Runner {
+            compiler_image,
+            runner_image,
+        }
     }
-    pub fn run(&self, stdin: File, stdout: File) -> Result<Child, SimulatorError> {
-        let compile = Command::new("timeout".to_owned())
-            .args([
-                "--signal=KILL",
-                COMPILATION_TIME_LIMIT,
-                "docker",
-                "run",
-                &format!("--memory={}", COMPILATION_MEMORY_LIMIT),
-                &format!("--memory-swap={}", COMPILATION_MEMORY_LIMIT),
-                "--cpus=2",
-                "--rm",
-                "-v",
-                format!("{}/run.cpp:/player_code/run.cpp", self.current_dir.as_str()).as_str(),
-                "-v",
-                format!("{}/run:/player_code/run", self.current_dir.as_str()).as_str(),
-                "ghcr.io/delta/codecharacter-cpp-compiler:latest",
-            ])
-            .current_dir(&self.current_dir.to_owned())
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| {
-                SimulatorError::UnidentifiedError(format!(
-                    "Couldnt spawn compilation command: {}",
-                    err
-                ))
-            })?;
-
-        let _ = handle_process(compile, true, |x| SimulatorError::CompilationError(x))?;
+}
 
-        Command::new("timeout".to_owned())
-            .args([
-                "--signal=KILL",
-                RUNTIME_TIME_LIMIT,
-                "docker",
-                "run",
-                &format!("--memory={}", RUNTIME_MEMORY_LIMIT),
-                &format!("--memory-swap={}", RUNTIME_MEMORY_LIMIT),
-                "--cpus=1",
-                "--rm",
-                "-i",
-                "-v",
-                format!("{}/run:/player_code", self.current_dir.as_str()).as_str(),
-                "ghcr.io/delta/codecharacter-cpp-runner:latest",
-            ])
-            .current_dir(&self.current_dir.to_owned())
-            .stdin(stdin)
-            .stdout(stdout)
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| {
-                SimulatorError::UnidentifiedError(format!(
-                    "Couldnt spawn the C++ runner process: {}",
-                    err
-                ))
-            })
+impl LanguageRunner for Runner {
+    fn name(&self) -> &'static str {
+        "C++"
+    }
+    fn boilerplate_dir(&self) -> &'static str {
+        "player_code/cpp"
+    }
+    fn source_file(&self) -> &'static str {
+        "run.cpp"
+    }
+    fn compiler_image(&self) -> Option<&str> {
+        Some(&self.compiler_image)
+    }
+    fn compile_mounts(&self) -> Vec<Mount> {
+        vec![
+            Mount::new("run.cpp", "/player_code/run.cpp"),
+            Mount::new("run", "/player_code/run"),
+        ]
+    }
+    fn native_compile_command(&self) -> Vec<String> {
+        ["sh", "-c", "g++ -std=c++17 -O2 -o player *.cpp"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect()
+    }
+    fn flat_source_extension(&self) -> Option<&'static str> {
+        Some("cpp")
+    }
+    fn artifacts(&self) -> Vec<&'static str> {
+        vec!["run"]
+    }
+    fn native_artifacts(&self) -> Vec<&'static str> {
+        vec!["player"]
+    }
+    fn runner_image(&self) -> &str {
+        &self.runner_image
+    }
+    fn run_mounts(&self) -> Vec<Mount> {
+        vec![Mount::new("run", "/player_code")]
+    }
+    fn native_run_command(&self) -> Vec<String> {
+        vec!["./player".to_owned()]
     }
 }
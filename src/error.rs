@@ -5,4 +5,43 @@ pub enum SimulatorError {
     UnidentifiedError(String),
     FifoCreationError(String),
     TimeOutError(String),
+    MemoryLimitExceeded(String),
+    InvalidRequestError(String),
+    /// Every problem found in the request, one per line
+    ValidationError(String),
+}
+
+impl SimulatorError {
+    /// Name of the variant, used as a metric label
+    pub fn name(&self) -> &'static str {
+        match self {
+            SimulatorError::CompilationError(_) => "CompilationError",
+            SimulatorError::RuntimeError(_) => "RuntimeError",
+            SimulatorError::UnidentifiedError(_) => "UnidentifiedError",
+            SimulatorError::FifoCreationError(_) => "FifoCreationError",
+            SimulatorError::TimeOutError(_) => "TimeOutError",
+            SimulatorError::MemoryLimitExceeded(_) => "MemoryLimitExceeded",
+            SimulatorError::InvalidRequestError(_) => "InvalidRequestError",
+            SimulatorError::ValidationError(_) => "ValidationError",
+        }
+    }
+
+    /// Prefixes the message with the player it concerns, for games with more than one player
+    pub fn for_player(self, player: usize) -> Self {
+        let prefix = |e: String| format!("Player {}: {}", player, e);
+        match self {
+            SimulatorError::CompilationError(e) => SimulatorError::CompilationError(prefix(e)),
+            SimulatorError::RuntimeError(e) => SimulatorError::RuntimeError(prefix(e)),
+            SimulatorError::UnidentifiedError(e) => SimulatorError::UnidentifiedError(prefix(e)),
+            SimulatorError::FifoCreationError(e) => SimulatorError::FifoCreationError(prefix(e)),
+            SimulatorError::TimeOutError(e) => SimulatorError::TimeOutError(prefix(e)),
+            SimulatorError::MemoryLimitExceeded(e) => {
+                SimulatorError::MemoryLimitExceeded(prefix(e))
+            }
+            SimulatorError::InvalidRequestError(e) => {
+                SimulatorError::InvalidRequestError(prefix(e))
+            }
+            SimulatorError::ValidationError(e) => SimulatorError::ValidationError(prefix(e)),
+        }
+    }
 }
@@ -27,7 +27,7 @@ impl Fifo {
         flags.remove(OFlag::O_NONBLOCK);
         fcntl::fcntl(fd, FcntlArg::F_SETFL(flags))
             .map_err(|e| SimulatorError::FifoCreationError(format!("{}", e)))?;
-        return Ok(());
+        Ok(())
     }
     fn setup_pipe(f: &str) -> Result<(File, File), SimulatorError> {
         Fifo::open_fifo(f)?;
@@ -105,11 +105,11 @@ mod fifo_tests {
         {
             let python_code = "import sys\nsys.stdout.write(sys.stdin.readline())";
             let mut temp_python_file = File::create("temp_py.py").unwrap();
-            temp_python_file.write(python_code.as_bytes()).unwrap();
+            temp_python_file.write_all(python_code.as_bytes()).unwrap();
         }
 
         match Command::new("python3")
-            .args(&["temp_py.py"])
+            .args(["temp_py.py"])
             .stdout(Stdio::piped())
             .stdin(fin)
             .spawn()
@@ -119,8 +119,8 @@ mod fifo_tests {
             Ok(out) => {
                 assert_eq!("Hello World", String::from_utf8(out.stdout).unwrap().trim());
             }
-            Err(_) => {
-                assert!(false);
+            Err(e) => {
+                panic!("{}", e);
             }
         }
         let _ = remove_file("temp_py.py");
@@ -1,46 +1,218 @@
+use std::{
+    io,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+
+/// Directory under the root the directories of failed games are moved to
+const FAILED_DIR: &str = "failed-games";
+/// Game ids are cut to this many characters in directory names
+const MAX_ID_LENGTH: usize = 64;
+
+static NEXT_DIR: AtomicU64 = AtomicU64::new(0);
+
+/// Working directory of one attempt at a game, removed when dropped.
+///
+/// Unless the game succeeded it is kept in `<root>/failed-games` for `retain_failed`
+/// instead, when that isn't zero.
 pub struct GameDir {
     full_path: String,
+    root: String,
+    retain_failed: Duration,
+    succeeded: bool,
 }
 
 impl GameDir {
-    pub fn new(game_id: &str) -> Option<Self> {
-        std::fs::create_dir(format!("/tmp/{}", game_id)).ok()?;
-        Some(GameDir {
-            full_path: format!("/tmp/{}", game_id),
+    pub fn new(root: &str, game_id: &str, retain_failed: Duration) -> io::Result<Self> {
+        let name = sanitize(game_id);
+        // ids are redelivered and directories of previous runs can be left behind, the
+        // suffix is bumped until an unused one is found
+        let full_path = loop {
+            let full_path = format!(
+                "{}/{}-{}-{}",
+                root,
+                name,
+                std::process::id(),
+                NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+            );
+            match std::fs::create_dir(&full_path) {
+                Ok(()) => break full_path,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        if !retain_failed.is_zero() {
+            remove_expired(&Path::new(root).join(FAILED_DIR), retain_failed);
+        }
+        Ok(GameDir {
+            full_path,
+            root: root.to_owned(),
+            retain_failed,
+            succeeded: false,
         })
     }
     pub fn get_path(&self) -> &str {
         &self.full_path
     }
+    /// The game ran to the end, its directory is removed even if failed ones are kept
+    pub fn succeeded(&mut self) {
+        self.succeeded = true;
+    }
+
+    /// Moves the directory to `<root>/failed-games/<unix time>-<name>`
+    fn retain(&self) -> io::Result<()> {
+        let failed_dir = Path::new(&self.root).join(FAILED_DIR);
+        std::fs::create_dir_all(&failed_dir)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let name = Path::new(&self.full_path)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let retained = failed_dir.join(format!("{}-{}", now, name));
+        std::fs::rename(&self.full_path, &retained)?;
+        info!(
+            "Kept the directory of a failed game in {}",
+            retained.display()
+        );
+        Ok(())
+    }
 }
 impl Drop for GameDir {
     fn drop(&mut self) {
+        if !self.succeeded && !self.retain_failed.is_zero() {
+            match self.retain() {
+                Ok(()) => return,
+                Err(e) => warn!("Failed to keep game directory {}: {}", self.full_path, e),
+            }
+        }
         let _ = std::fs::remove_dir_all(self.get_path());
     }
 }
 
+/// Only keeps the characters that are safe in a path, so that ids can't escape the root
+fn sanitize(game_id: &str) -> String {
+    let name = game_id
+        .chars()
+        .take(MAX_ID_LENGTH)
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if name.is_empty() {
+        "game".to_owned()
+    } else {
+        name
+    }
+}
+
+/// Removes the directories of failed games kept for longer than `retain_failed`
+fn remove_expired(failed_dir: &Path, retain_failed: Duration) {
+    let entries = match std::fs::read_dir(failed_dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    for entry in entries.flatten() {
+        let retained_at = entry
+            .file_name()
+            .to_string_lossy()
+            .split('-')
+            .next()
+            .and_then(|time| time.parse::<u64>().ok());
+        if let Some(retained_at) = retained_at {
+            if now.saturating_sub(retained_at) >= retain_failed.as_secs() {
+                let _ = std::fs::remove_dir_all(entry.path());
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{path::Path, io::Write};
+    use std::{io::Write, path::Path, time::Duration};
 
-    use super::GameDir;
+    use super::{remove_expired, sanitize, GameDir};
 
     #[test]
     fn dir_creation_and_deletion_check() {
         let game_id = "030af985-f4b5-4914-94d8-e559576449e3";
-        let match_dir_handle = GameDir::new(&game_id).unwrap();
+        let match_dir_handle = GameDir::new("/tmp", game_id, Duration::ZERO).unwrap();
 
         let full_path = match_dir_handle.get_path().to_owned();
 
-        let player_code_file = format!("{}/something",full_path);
+        let player_code_file = format!("{}/something", full_path);
         let _ = std::fs::File::create(player_code_file).and_then(|mut file| {
             file.write_all("Hello".as_bytes())
                 .and_then(|_| file.sync_all())
         });
         assert!(Path::new(&full_path).exists());
 
+        // a redelivered game gets its own directory
+        let duplicate = GameDir::new("/tmp", game_id, Duration::ZERO).unwrap();
+        assert_ne!(duplicate.get_path(), full_path);
+
         drop(match_dir_handle);
 
         assert!(!Path::new(&full_path).exists());
     }
+
+    #[test]
+    fn ids_are_sanitized() {
+        assert_eq!(sanitize("../../etc"), "______etc");
+        assert_eq!(sanitize("a/b c"), "a_b_c");
+        assert_eq!(sanitize(""), "game");
+        assert_eq!(sanitize(&"x".repeat(100)).len(), 64);
+
+        let dir = GameDir::new("/tmp", "../escape", Duration::ZERO).unwrap();
+        assert_eq!(Path::new(dir.get_path()).parent(), Some(Path::new("/tmp")));
+    }
+
+    #[test]
+    fn failed_dirs_are_kept() {
+        let root = "/tmp/cc-driver-game-dir-test";
+        let _ = std::fs::remove_dir_all(root);
+        std::fs::create_dir_all(format!("{}/failed-games/0-expired", root)).unwrap();
+
+        let mut succeeded = GameDir::new(root, "ok", Duration::from_secs(60)).unwrap();
+        // the expired directory was removed when the game started
+        assert!(!Path::new(root).join("failed-games/0-expired").exists());
+        succeeded.succeeded();
+        drop(succeeded);
+
+        let failed = GameDir::new(root, "failed", Duration::from_secs(60)).unwrap();
+        std::fs::write(format!("{}/log", failed.get_path()), "output").unwrap();
+        drop(failed);
+
+        let retained = std::fs::read_dir(format!("{}/failed-games", root))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(retained.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(retained[0].join("log")).unwrap(),
+            "output"
+        );
+
+        remove_expired(
+            &Path::new(root).join("failed-games"),
+            Duration::from_secs(60),
+        );
+        assert!(retained[0].exists());
+        remove_expired(&Path::new(root).join("failed-games"), Duration::ZERO);
+        assert!(!retained[0].exists());
+        std::fs::remove_dir_all(root).unwrap();
+    }
 }
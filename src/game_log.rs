@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+/// Format of `GameResult::log`, the text log is kept as the default for older clients
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum LogFormat {
+    #[default]
+    #[serde(alias = "text")]
+    Text,
+    #[serde(alias = "json")]
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format {}, expected text or json", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TurnLog {
+    pub turn: usize,
+    /// What the player printed during the turn
+    pub prints: Vec<String>,
+    /// Coins left at the end of the turn
+    pub coins: Option<u32>,
+    pub destruction: Option<f64>,
+    /// Simulator lines that aren't understood by the driver, in order
+    pub events: Vec<String>,
+}
+
+/// The game log in a form that can be serialized as JSON instead of being re-parsed
+#[derive(Serialize, Debug, PartialEq, Default)]
+pub struct GameLog {
+    /// Simulator lines printed before the first turn
+    pub preamble: Vec<String>,
+    pub turns: Vec<TurnLog>,
+}
+
+impl GameLog {
+    pub fn start_turn(&mut self, turn: usize, prints: Vec<String>) {
+        self.turns.push(TurnLog {
+            turn,
+            prints,
+            coins: None,
+            destruction: None,
+            events: vec![],
+        });
+    }
+
+    /// `line` is kept as is when no turn has been started yet
+    pub fn coins(&mut self, coins: u32, line: &str) {
+        match self.turns.last_mut() {
+            Some(turn) => turn.coins = Some(coins),
+            None => self.other(line),
+        }
+    }
+
+    pub fn destruction(&mut self, percentage: f64, line: &str) {
+        match self.turns.last_mut() {
+            Some(turn) => turn.destruction = Some(percentage),
+            None => self.other(line),
+        }
+    }
+
+    pub fn other(&mut self, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+        match self.turns.last_mut() {
+            Some(turn) => turn.events.push(line.to_owned()),
+            None => self.preamble.push(line.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameLog, LogFormat};
+
+    #[test]
+    fn json_serialization_test() {
+        let mut log = GameLog::default();
+        log.other("MAP, 64 64");
+        log.coins(500, "COINS, 500");
+        log.start_turn(1, vec!["hello".to_owned()]);
+        log.other("SPAWN, 1, 0, 0");
+        log.coins(499, "COINS, 499");
+        log.destruction(12.5, "DESTRUCTION, 12.5%");
+        log.other("");
+        log.start_turn(2, vec![]);
+
+        assert_eq!(
+            serde_json::to_string(&log).unwrap(),
+            r#"{"preamble":["MAP, 64 64","COINS, 500"],"turns":[{"turn":1,"prints":["hello"],"coins":499,"destruction":12.5,"events":["SPAWN, 1, 0, 0"]},{"turn":2,"prints":[],"coins":null,"destruction":null,"events":[]}]}"#
+        );
+    }
+
+    #[test]
+    fn log_format_parsing() {
+        assert_eq!(
+            serde_json::from_str::<LogFormat>(r#""JSON""#).unwrap(),
+            LogFormat::Json
+        );
+        assert_eq!("text".parse::<LogFormat>(), Ok(LogFormat::Text));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{backend::Mount, runner::LanguageRunner};
+
+/// The boilerplate is a go module, built without fetching anything
+pub struct Runner {
+    compiler_image: String,
+    runner_image: String,
+}
+
+impl Runner {
+    pub fn new(compiler_image: String, runner_image: String) -> Self {
+        Runner {
+            compiler_image,
+            runner_image,
+        }
+    }
+}
+
+impl LanguageRunner for Runner {
+    fn name(&self) -> &'static str {
+        "go"
+    }
+    fn boilerplate_dir(&self) -> &'static str {
+        "player_code/go"
+    }
+    fn source_file(&self) -> &'static str {
+        "run.go"
+    }
+    fn compiler_image(&self) -> Option<&str> {
+        Some(&self.compiler_image)
+    }
+    fn compile_mounts(&self) -> Vec<Mount> {
+        vec![
+            Mount::new("run.go", "/player_code/run.go"),
+            Mount::new("run", "/player_code/run"),
+        ]
+    }
+    fn native_compile_command(&self) -> Vec<String> {
+        [
+            "sh",
+            "-c",
+            "GOPROXY=off GOCACHE=\"$PWD/.gocache\" go build -o player .",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+    }
+    fn artifacts(&self) -> Vec<&'static str> {
+        vec!["run"]
+    }
+    fn native_artifacts(&self) -> Vec<&'static str> {
+        vec!["player"]
+    }
+    fn runner_image(&self) -> &str {
+        &self.runner_image
+    }
+    fn run_mounts(&self) -> Vec<Mount> {
+        vec![Mount::new("run", "/player_code")]
+    }
+    fn native_run_command(&self) -> Vec<String> {
+        vec!["./player".to_owned()]
+    }
+}
@@ -0,0 +1,525 @@
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, Read, Write},
+    net::SocketAddr,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use log::{info, warn};
+use lru::LruCache;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    cancel::cancellations,
+    config::HttpConfig,
+    create_error_response_for_id,
+    error::SimulatorError,
+    job::{Ack, Job, JobOutcome, StatusPublisher},
+    metrics::metrics,
+    request::DriverRequest,
+    response::{GameStatus, GameStatusEnum},
+    shutdown::shutdown,
+};
+
+/// Comment sent on idle event streams, so that closed connections are noticed
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Clone)]
+struct StatusEvent {
+    json: String,
+    /// The game is over, no more updates will follow
+    finished: bool,
+}
+
+struct Subscriber {
+    /// Only updates of this game are sent when set
+    game_id: Option<String>,
+    sender: Sender<StatusEvent>,
+}
+
+/// Receiving end of an event stream, unsubscribes once dropped
+struct Subscription<'a> {
+    store: &'a StatusStore,
+    id: u64,
+    events: Receiver<StatusEvent>,
+}
+
+impl Drop for Subscription<'_> {
+    fn drop(&mut self) {
+        self.store.subscribers.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Latest status of the games submitted over HTTP, every update is also sent to the
+/// open event streams
+pub struct StatusStore {
+    statuses: Mutex<LruCache<String, StatusEvent>>,
+    subscribers: Mutex<HashMap<u64, Subscriber>>,
+    next_subscriber: AtomicU64,
+}
+
+impl StatusStore {
+    pub fn new(max_retained_games: usize) -> Self {
+        StatusStore {
+            statuses: Mutex::new(LruCache::new(
+                NonZeroUsize::new(max_retained_games).unwrap_or(NonZeroUsize::MIN),
+            )),
+            subscribers: Mutex::new(HashMap::new()),
+            next_subscriber: AtomicU64::new(0),
+        }
+    }
+
+    fn get(&self, game_id: &str) -> Option<StatusEvent> {
+        self.statuses.lock().unwrap().get(game_id).cloned()
+    }
+
+    fn subscribe(&self, game_id: Option<String>) -> Subscription<'_> {
+        let (sender, events) = crossbeam_channel::unbounded();
+        let id = self.next_subscriber.fetch_add(1, Ordering::Relaxed);
+        self.subscribers
+            .lock()
+            .unwrap()
+            .insert(id, Subscriber { game_id, sender });
+        Subscription {
+            store: self,
+            id,
+            events,
+        }
+    }
+}
+
+impl StatusPublisher for StatusStore {
+    fn publish(&self, status: GameStatus) -> Result<(), SimulatorError> {
+        let event = StatusEvent {
+            json: serde_json::to_string(&status)
+                .map_err(|e| SimulatorError::UnidentifiedError(format!("{}", e)))?,
+            finished: matches!(
+                status.game_status,
+                GameStatusEnum::EXECUTED
+                    | GameStatusEnum::EXECUTE_ERROR
+                    | GameStatusEnum::CANCELLED
+            ),
+        };
+        self.statuses
+            .lock()
+            .unwrap()
+            .put(status.game_id.clone(), event.clone());
+        // streams unsubscribe once they end, those that already hung up are dropped here
+        // along with every subscriber of a game that is over
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|_, subscriber| match &subscriber.game_id {
+                Some(game_id) if *game_id != status.game_id => true,
+                Some(_) => subscriber.sender.send(event.clone()).is_ok() && !event.finished,
+                None => subscriber.sender.send(event.clone()).is_ok(),
+            });
+        Ok(())
+    }
+}
+
+enum Route {
+    Submit,
+    Status(String),
+    Cancel(String),
+    Events(Option<String>),
+    NotFound,
+}
+
+/// Embedded HTTP API, games submitted through it share the workers with the other
+/// transports
+pub struct HttpApi {
+    server: Server,
+    store: Arc<StatusStore>,
+    jobs: Sender<Job>,
+    max_queued_games: usize,
+    max_request_size: u64,
+    event_streams: Arc<AtomicUsize>,
+    max_event_streams: usize,
+}
+
+/// An open event stream, counted against `max_event_streams` until dropped
+struct EventStream(Arc<AtomicUsize>);
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl HttpApi {
+    pub fn bind(config: &HttpConfig, jobs: Sender<Job>) -> io::Result<Self> {
+        let server = Server::http(&config.address).map_err(io::Error::other)?;
+        Ok(HttpApi {
+            server,
+            store: Arc::new(StatusStore::new(config.max_retained_games)),
+            jobs,
+            max_queued_games: config.max_queued_games,
+            max_request_size: config.max_request_size_kb * 1024,
+            event_streams: Arc::new(AtomicUsize::new(0)),
+            max_event_streams: config.max_event_streams,
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Serves requests until the server is dropped, event streams get a thread each
+    pub fn run(self) {
+        info!("Serving the HTTP API on {:?}", self.server.server_addr());
+        for request in self.server.incoming_requests() {
+            let path = request.url().split('?').next().unwrap_or_default();
+            let segments = path.trim_matches('/').split('/').collect::<Vec<&str>>();
+            let route = match (request.method(), segments.as_slice()) {
+                (Method::Post, ["games"]) => Route::Submit,
+                (Method::Get, ["games", game_id]) => Route::Status(game_id.to_string()),
+                (Method::Delete, ["games", game_id]) => Route::Cancel(game_id.to_string()),
+                (Method::Get, ["games", game_id, "events"]) => {
+                    Route::Events(Some(game_id.to_string()))
+                }
+                (Method::Get, ["events"]) => Route::Events(None),
+                _ => Route::NotFound,
+            };
+
+            match route {
+                Route::Submit => self.submit(request),
+                Route::Status(game_id) => match self.store.get(&game_id) {
+                    Some(status) => respond(request, json_response(200, status.json)),
+                    None => respond(request, error_response(404, "Unknown game")),
+                },
+                // games submitted through the other transports can be cancelled too
+                Route::Cancel(game_id) if cancellations().cancel(&game_id) => respond(
+                    request,
+                    json_response(202, serde_json::json!({ "game_id": game_id }).to_string()),
+                ),
+                Route::Cancel(game_id) => match self.store.get(&game_id) {
+                    Some(_) => respond(request, error_response(409, "Game already finished")),
+                    None => respond(request, error_response(404, "Unknown game")),
+                },
+                Route::Events(Some(game_id)) if self.store.get(&game_id).is_none() => {
+                    respond(request, error_response(404, "Unknown game"))
+                }
+                // only this loop opens streams, so the count can't change in between
+                Route::Events(_)
+                    if self.event_streams.load(Ordering::Relaxed) >= self.max_event_streams =>
+                {
+                    respond(request, error_response(503, "Too many event streams"))
+                }
+                Route::Events(game_id) => {
+                    self.event_streams.fetch_add(1, Ordering::Relaxed);
+                    let stream = EventStream(Arc::clone(&self.event_streams));
+                    let store = Arc::clone(&self.store);
+                    std::thread::spawn(move || {
+                        let _stream = stream;
+                        if let Err(e) = stream_events(request, &store, game_id) {
+                            info!("Event stream closed: {}", e);
+                        }
+                    });
+                }
+                Route::NotFound => respond(request, error_response(404, "Not found")),
+            }
+        }
+    }
+
+    fn submit(&self, mut request: Request) {
+        let mut body = String::new();
+        if let Err(e) = request
+            .as_reader()
+            .take(self.max_request_size + 1)
+            .read_to_string(&mut body)
+        {
+            return respond(
+                request,
+                error_response(400, &format!("Failed to read the request: {}", e)),
+            );
+        }
+        if body.len() as u64 > self.max_request_size {
+            return respond(request, error_response(413, "Game request too large"));
+        }
+        let game_request = match DriverRequest::from_json(&body) {
+            Ok(game_request) => game_request,
+            Err(e) => {
+                return respond(
+                    request,
+                    error_response(400, &format!("Malformed game request: {}", e)),
+                )
+            }
+        };
+
+        if shutdown().requested() {
+            return respond(request, error_response(503, "The driver is shutting down"));
+        }
+        let game_id = game_request.game_id().to_owned();
+        if matches!(self.store.get(&game_id), Some(status) if !status.finished) {
+            return respond(request, error_response(409, "Game is already running"));
+        }
+        if self.jobs.len() >= self.max_queued_games {
+            return respond(request, error_response(503, "Too many queued games"));
+        }
+
+        let status = GameStatus {
+            game_id: game_id.clone(),
+            game_status: GameStatusEnum::IDLE,
+            game_result: None,
+            pvp_game_result: None,
+            error: None,
+        };
+        let body = serde_json::to_string(&status).unwrap_or_default();
+        let location = Header::from_bytes(&b"Location"[..], format!("/games/{}", game_id));
+        let _ = self.store.publish(status);
+
+        let store = Arc::clone(&self.store);
+        let ack = Ack::new(move |outcome| {
+            // nothing to retry from, the error is reported instead
+            let message = match outcome {
+                JobOutcome::Completed => return,
+                JobOutcome::Failed => "The driver failed while executing the game",
+                JobOutcome::NotStarted => "The driver shut down before the game was started",
+            };
+            let status = create_error_response_for_id(
+                &game_id,
+                SimulatorError::UnidentifiedError(message.to_owned()),
+            );
+            if let Some(error) = status.error {
+                metrics().error(error);
+            }
+            let _ = store.publish(status);
+        });
+        let job = Job::new(
+            game_request,
+            ack,
+            Arc::clone(&self.store) as Arc<dyn StatusPublisher>,
+        );
+        if self.jobs.send(job).is_err() {
+            return respond(request, error_response(503, "No workers are running"));
+        }
+
+        let mut response = json_response(202, body);
+        if let Ok(location) = location {
+            response.add_header(location);
+        }
+        respond(request, response)
+    }
+}
+
+/// Streams status updates as server-sent events, a stream of a single game starts with
+/// its current status and ends with its final one
+fn stream_events(request: Request, store: &StatusStore, game_id: Option<String>) -> io::Result<()> {
+    let subscription = store.subscribe(game_id.clone());
+    // the response is written by hand, tiny_http buffers chunked bodies
+    let mut writer = request.into_writer();
+    writer.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+    )?;
+    writer.flush()?;
+
+    if let Some(status) = game_id.as_deref().and_then(|game_id| store.get(game_id)) {
+        write_event(&mut writer, &status)?;
+        if status.finished {
+            return Ok(());
+        }
+    }
+    loop {
+        match subscription.events.recv_timeout(KEEP_ALIVE_INTERVAL) {
+            Ok(status) => {
+                write_event(&mut writer, &status)?;
+                if game_id.is_some() && status.finished {
+                    return Ok(());
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                writer.write_all(b": keep-alive\n\n")?;
+                writer.flush()?;
+            }
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+fn write_event(writer: &mut impl Write, status: &StatusEvent) -> io::Result<()> {
+    write!(writer, "event: status\ndata: {}\n\n", status.json)?;
+    writer.flush()
+}
+
+fn json_response(status: u16, json: String) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(json)
+        .with_status_code(status)
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                .expect("valid header"),
+        )
+}
+
+fn error_response(status: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    json_response(status, serde_json::json!({ "error": message }).to_string())
+}
+
+fn respond(request: Request, response: Response<Cursor<Vec<u8>>>) {
+    if let Err(e) = request.respond(response) {
+        warn!("Failed to send HTTP response: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    };
+
+    use super::{HttpApi, StatusStore};
+    use crate::{
+        cancel::cancellations,
+        config::HttpConfig,
+        create_cancelled_response_for_id, create_error_response_for_id,
+        error::SimulatorError,
+        job::{Job, JobOutcome, StatusPublisher},
+        response::{GameStatus, GameStatusEnum},
+    };
+
+    fn status(game_id: &str, game_status: GameStatusEnum) -> GameStatus {
+        GameStatus {
+            game_status,
+            ..create_cancelled_response_for_id(game_id)
+        }
+    }
+
+    #[test]
+    fn subscribers_are_dropped_when_done() {
+        let store = StatusStore::new(10);
+        let subscribers = || store.subscribers.lock().unwrap().len();
+        let all = store.subscribe(None);
+        let game = store.subscribe(Some("a".to_owned()));
+        let other = store.subscribe(Some("b".to_owned()));
+        assert_eq!(subscribers(), 3);
+
+        drop(other);
+        assert_eq!(subscribers(), 2);
+        store
+            .publish(status("a", GameStatusEnum::EXECUTING))
+            .unwrap();
+        assert_eq!(subscribers(), 2);
+        store
+            .publish(status("a", GameStatusEnum::EXECUTED))
+            .unwrap();
+        assert_eq!(subscribers(), 1);
+        assert_eq!(game.events.try_iter().count(), 2);
+        assert_eq!(all.events.try_iter().count(), 2);
+        drop(game);
+        drop(all);
+        assert_eq!(subscribers(), 0);
+    }
+
+    fn send(addr: SocketAddr, method: &str, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn games_are_submitted_and_tracked() {
+        let (jobs, receiver) = crossbeam_channel::unbounded::<Job>();
+        let api = HttpApi::bind(
+            &HttpConfig {
+                enabled: true,
+                address: "127.0.0.1:0".to_owned(),
+                ..Default::default()
+            },
+            jobs,
+        )
+        .unwrap();
+        let addr = api.local_addr().unwrap();
+        std::thread::spawn(move || api.run());
+
+        let request = r#"{"game_id":"http-1","parameters":{"attackers":[],"defenders":[],"no_of_turns":500,"no_of_coins":1000},"source_code":"","language":"CPP","map":"[[0]]"}"#;
+        let response = send(addr, "POST", "/games", request);
+        assert!(response.starts_with("HTTP/1.1 202"), "{}", response);
+        assert!(response.contains("Location: /games/http-1"));
+        assert!(response.contains(r#""game_status":"IDLE""#));
+
+        let job = receiver.try_recv().unwrap();
+        assert_eq!(job.request.game_id(), "http-1");
+
+        assert!(send(addr, "POST", "/games", request).starts_with("HTTP/1.1 409"));
+        assert!(send(addr, "POST", "/games", "{}").starts_with("HTTP/1.1 400"));
+        assert!(send(addr, "GET", "/games/unknown", "").starts_with("HTTP/1.1 404"));
+        assert!(send(addr, "DELETE", "/games/unknown", "").starts_with("HTTP/1.1 404"));
+        assert!(send(addr, "DELETE", "/games/http-1", "").starts_with("HTTP/1.1 202"));
+        assert!(cancellations().is_cancelled("http-1"));
+
+        let events = std::thread::spawn(move || send(addr, "GET", "/games/http-1/events", ""));
+        job.ack.complete(JobOutcome::Failed);
+        let events = events.join().unwrap();
+        assert!(events.contains("Content-Type: text/event-stream"));
+        assert!(events.contains(r#"data: {"game_id":"http-1","game_status":"EXECUTE_ERROR""#));
+        assert!(send(addr, "DELETE", "/games/http-1", "").starts_with("HTTP/1.1 409"));
+
+        job.publisher
+            .publish(create_error_response_for_id(
+                "http-1",
+                SimulatorError::RuntimeError("oops".to_owned()),
+            ))
+            .unwrap();
+        let status = send(addr, "GET", "/games/http-1", "");
+        assert!(status.starts_with("HTTP/1.1 200"));
+        assert!(status.contains("Runtime Error!"));
+    }
+
+    #[test]
+    fn event_streams_are_capped() {
+        let (jobs, _receiver) = crossbeam_channel::unbounded::<Job>();
+        let api = HttpApi::bind(
+            &HttpConfig {
+                enabled: true,
+                address: "127.0.0.1:0".to_owned(),
+                max_event_streams: 1,
+                ..Default::default()
+            },
+            jobs,
+        )
+        .unwrap();
+        let addr = api.local_addr().unwrap();
+        let streams = Arc::clone(&api.event_streams);
+        let store = Arc::clone(&api.store);
+        std::thread::spawn(move || api.run());
+
+        let mut open = TcpStream::connect(addr).unwrap();
+        write!(open, "GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut head = [0; 15];
+        open.read_exact(&mut head).unwrap();
+        assert_eq!(&head, b"HTTP/1.1 200 OK");
+        assert!(send(addr, "GET", "/events", "").starts_with("HTTP/1.1 503"));
+
+        // the stream only notices the hang up once it fails to write a status update
+        drop(open);
+        for _ in 0..100 {
+            if streams.load(Ordering::Relaxed) == 0 {
+                break;
+            }
+            store
+                .publish(status("capped", GameStatusEnum::EXECUTING))
+                .unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(streams.load(Ordering::Relaxed), 0);
+    }
+}
@@ -1,79 +1,63 @@
-use std::{
-    fs::File,
-    process::{Child, Command, Stdio},
-};
-
-use crate::{
-    error::SimulatorError, handle_process, COMPILATION_MEMORY_LIMIT, COMPILATION_TIME_LIMIT,
-    RUNTIME_MEMORY_LIMIT, RUNTIME_TIME_LIMIT,
-};
+use crate::{backend::Mount, runner::LanguageRunner};
 
 pub struct Runner {
-    current_dir: String,
+    compiler_image: String,
+    runner_image: String,
 }
+
 impl Runner {
-    pub fn new(current_dir: String) -> Self {
-        Runner { current_dir }
+    pub fn new(compiler_image: String, runner_image: String) -> Self {
+        Runner {
+            compiler_image,
+            runner_image,
+        }
     }
-    pub fn run(&self, stdin: File, stdout: File) -> Result<Child, SimulatorError> {
-        let compile = Command::new("timeout".to_owned())
-            .args([
-                "--signal=KILL",
-                COMPILATION_TIME_LIMIT,
-                "docker",
-                "run",
-                &format!("--memory={}", COMPILATION_MEMORY_LIMIT),
-                &format!("--memory-swap={}", COMPILATION_MEMORY_LIMIT),
-                "--cpus=1.5",
-                "--rm",
-                "-v",
-                format!(
-                    "{}/Run.java:/player_code/Run.java",
-                    self.current_dir.as_str()
-                )
-                .as_str(),
-                "-v",
-                format!("{}/run.jar:/player_code/run.jar", self.current_dir.as_str()).as_str(),
-                "ghcr.io/delta/codecharacter-java-compiler:latest",
-            ])
-            .current_dir(&self.current_dir.to_owned())
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| {
-                SimulatorError::UnidentifiedError(format!(
-                    "Couldnt spawn compilation command: {}",
-                    err
-                ))
-            })?;
-
-        let _ = handle_process(compile, true, |x| SimulatorError::CompilationError(x))?;
+}
 
-        Command::new("timeout".to_owned())
-            .args([
-                "--signal=KILL",
-                RUNTIME_TIME_LIMIT,
-                "docker",
-                "run",
-                &format!("--memory={}", RUNTIME_MEMORY_LIMIT),
-                &format!("--memory-swap={}", RUNTIME_MEMORY_LIMIT),
-                "--cpus=1",
-                "--rm",
-                "-i",
-                "-v",
-                format!("{}/run.jar:/run.jar", self.current_dir.as_str()).as_str(),
-                "ghcr.io/delta/codecharacter-java-runner:latest",
-            ])
-            .current_dir(&self.current_dir.to_owned())
-            .stdin(stdin)
-            .stdout(stdout)
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| {
-                SimulatorError::UnidentifiedError(format!(
-                    "Couldnt spawn the java runner process: {}",
-                    err
-                ))
-            })
+impl LanguageRunner for Runner {
+    fn name(&self) -> &'static str {
+        "java"
+    }
+    fn boilerplate_dir(&self) -> &'static str {
+        "player_code/java"
+    }
+    fn source_file(&self) -> &'static str {
+        "Run.java"
+    }
+    fn compiler_image(&self) -> Option<&str> {
+        Some(&self.compiler_image)
+    }
+    fn compile_mounts(&self) -> Vec<Mount> {
+        vec![
+            Mount::new("Run.java", "/player_code/Run.java"),
+            Mount::new("run.jar", "/player_code/run.jar"),
+        ]
+    }
+    fn compile_cpus(&self) -> &'static str {
+        "1.5"
+    }
+    fn native_compile_command(&self) -> Vec<String> {
+        ["javac", "-d", "classes", "Run.java"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect()
+    }
+    fn artifacts(&self) -> Vec<&'static str> {
+        vec!["run.jar"]
+    }
+    fn native_artifacts(&self) -> Vec<&'static str> {
+        vec!["classes"]
+    }
+    fn runner_image(&self) -> &str {
+        &self.runner_image
+    }
+    fn run_mounts(&self) -> Vec<Mount> {
+        vec![Mount::new("run.jar", "/run.jar")]
+    }
+    fn native_run_command(&self) -> Vec<String> {
+        ["java", "-cp", "classes", "Run"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect()
     }
 }
@@ -0,0 +1,78 @@
+use std::{sync::Arc, time::Instant};
+
+use log::error;
+
+use crate::{
+    cancel::cancellations, error::SimulatorError, metrics::metrics, request::DriverRequest,
+    response::GameStatus,
+};
+
+/// What the transport should do with a request once the worker is done with it
+#[derive(Debug, PartialEq)]
+pub enum JobOutcome {
+    /// The final status reached the transport
+    Completed,
+    /// Something went wrong on our side, the transport may retry the request
+    Failed,
+    /// The driver shut down before the game was started, the transport may hand it to
+    /// another driver
+    NotStarted,
+}
+
+/// Where the status updates of a game are sent, depends on the transport the request
+/// came from
+pub trait StatusPublisher: Send + Sync {
+    fn publish(&self, status: GameStatus) -> Result<(), SimulatorError>;
+    /// Publishes the last status of a game. `ack` is completed once the status can't be
+    /// lost anymore, which may be after this returns.
+    fn publish_final(&self, status: GameStatus, ack: Ack) {
+        let game_id = status.game_id.clone();
+        match self.publish(status) {
+            Ok(()) => ack.complete(JobOutcome::Completed),
+            Err(e) => {
+                error!("Failed to publish result for {}: {:?}", game_id, e);
+                ack.complete(JobOutcome::Failed)
+            }
+        }
+    }
+}
+
+/// Handle used by a worker to report back to the transport the request came from
+pub struct Ack(Box<dyn FnOnce(JobOutcome) + Send>);
+
+impl Ack {
+    pub fn new(on_complete: impl FnOnce(JobOutcome) + Send + 'static) -> Self {
+        Ack(Box::new(on_complete))
+    }
+    pub fn complete(self, outcome: JobOutcome) {
+        (self.0)(outcome)
+    }
+}
+
+/// A game waiting for a worker, shared by every transport
+pub struct Job {
+    pub request: DriverRequest,
+    pub ack: Ack,
+    pub publisher: Arc<dyn StatusPublisher>,
+    pub queued_at: Instant,
+}
+
+impl Job {
+    /// The game can be cancelled until the job is acked
+    pub fn new(request: DriverRequest, ack: Ack, publisher: Arc<dyn StatusPublisher>) -> Self {
+        for language in request.languages() {
+            metrics().game_received(language);
+        }
+        let game_id = request.game_id().to_owned();
+        cancellations().track(&game_id);
+        Job {
+            request,
+            ack: Ack::new(move |outcome| {
+                cancellations().untrack(&game_id);
+                ack.complete(outcome);
+            }),
+            publisher,
+            queued_at: Instant::now(),
+        }
+    }
+}
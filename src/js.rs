@@ -0,0 +1,91 @@
+use crate::{backend::Mount, runner::LanguageRunner};
+
+/// Runs javascript with node, typescript is transpiled to javascript first
+pub struct Runner {
+    /// Image with the typescript compiler, `None` for javascript
+    compiler_image: Option<String>,
+    runner_image: String,
+}
+
+impl Runner {
+    pub fn javascript(runner_image: String) -> Self {
+        Runner {
+            compiler_image: None,
+            runner_image,
+        }
+    }
+
+    pub fn typescript(compiler_image: String, runner_image: String) -> Self {
+        Runner {
+            compiler_image: Some(compiler_image),
+            runner_image,
+        }
+    }
+
+    fn is_typescript(&self) -> bool {
+        self.compiler_image.is_some()
+    }
+}
+
+impl LanguageRunner for Runner {
+    fn name(&self) -> &'static str {
+        if self.is_typescript() {
+            "typescript"
+        } else {
+            "javascript"
+        }
+    }
+    fn boilerplate_dir(&self) -> &'static str {
+        if self.is_typescript() {
+            "player_code/typescript"
+        } else {
+            "player_code/javascript"
+        }
+    }
+    fn source_file(&self) -> &'static str {
+        if self.is_typescript() {
+            "run.ts"
+        } else {
+            "run.js"
+        }
+    }
+    fn compiler_image(&self) -> Option<&str> {
+        self.compiler_image.as_deref()
+    }
+    fn compile_mounts(&self) -> Vec<Mount> {
+        vec![
+            Mount::new("run.ts", "/player_code/run.ts"),
+            Mount::new("run", "/player_code/run"),
+        ]
+    }
+    fn native_compile_command(&self) -> Vec<String> {
+        ["tsc", "--outDir", "build", "run.ts"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect()
+    }
+    fn artifacts(&self) -> Vec<&'static str> {
+        vec!["run"]
+    }
+    fn native_artifacts(&self) -> Vec<&'static str> {
+        vec!["build"]
+    }
+    fn runner_image(&self) -> &str {
+        &self.runner_image
+    }
+    fn run_mounts(&self) -> Vec<Mount> {
+        if self.is_typescript() {
+            vec![Mount::new("run", "/player_code")]
+        } else {
+            vec![Mount::new("run.js", "/player_code/run.js")]
+        }
+    }
+    fn native_run_command(&self) -> Vec<String> {
+        let entry = if self.is_typescript() {
+            "build/run.js"
+        } else {
+            "run.js"
+        };
+        vec!["node".to_owned(), entry.to_owned()]
+    }
+}
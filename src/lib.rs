@@ -1,35 +1,54 @@
-use std::{collections::HashMap, io::Read, os::unix::prelude::ExitStatusExt, process::Child};
+use std::{collections::HashMap, io::Read, os::unix::prelude::ExitStatusExt};
 
+use backend::{Process, ProcessOutput};
 use error::SimulatorError;
+use game_log::{GameLog, LogFormat};
 use log::error;
-use response::{GameResult, GameStatusEnum};
+use response::{GameResourceUsage, GameResult, GameStatusEnum, PvPGameResult, ResourceUsage};
+pub mod backend;
+pub mod cache;
+pub mod cancel;
+pub mod config;
 pub mod cpp;
 pub mod error;
 pub mod fifo;
 pub mod game_dir;
+pub mod game_log;
+pub mod go;
+pub mod http;
 pub mod java;
+pub mod job;
+pub mod js;
+pub mod metrics;
 pub mod mq;
+pub mod native;
+pub mod protocol;
 pub mod py;
 pub mod request;
 pub mod response;
+pub mod runner;
+pub mod rust;
+pub mod shutdown;
 pub mod simulator;
+pub mod transcript;
 pub mod utils;
+pub mod validation;
 
 // maximum size for log will be around 200KBs, everything after that is ignored
 const MAXLOGSIZE: usize = 200000;
 const SIGKILL: i32 = 9;
-const COMPILATION_TIME_LIMIT: &'static str = "5";
-const RUNTIME_TIME_LIMIT: &'static str = "10";
-const COMPILATION_MEMORY_LIMIT: &'static str = "300m";
-const RUNTIME_MEMORY_LIMIT: &'static str = "100m";
 
 pub fn handle_process(
-    proc: Child,
+    proc: Process,
     is_player_process: bool,
     make_err: fn(String) -> SimulatorError,
-) -> Result<String, SimulatorError> {
+) -> Result<(String, ResourceUsage), SimulatorError> {
     match proc.wait_with_output() {
-        Ok(out) => {
+        Ok(ProcessOutput {
+            output: out,
+            oom_killed,
+            usage,
+        }) => {
             let logs_extraction_result: Result<String, std::io::Error> = if is_player_process {
                 let mut logs = String::new();
                 out.stderr
@@ -37,31 +56,37 @@ pub fn handle_process(
                     .read_to_string(&mut logs)
                     .map(|_| logs)
             } else {
-                String::from_utf8(out.stderr)
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e)))
+                String::from_utf8(out.stderr).map_err(|e| std::io::Error::other(format!("{}", e)))
             };
             if out.status.success() {
                 match logs_extraction_result {
-                    Err(e) => Err(SimulatorError::UnidentifiedError(
-                        format!("Error during log extraction: {}", e).to_owned(),
-                    )),
-                    Ok(logs) => Ok(logs),
+                    Err(e) => Err(SimulatorError::UnidentifiedError(format!(
+                        "Error during log extraction: {}",
+                        e
+                    ))),
+                    Ok(logs) => Ok((logs, usage)),
                 }
             } else {
-                if let Some(sig) = out.status.signal() {
-                    if sig == SIGKILL {
-                        return Err(SimulatorError::TimeOutError("Process took longer than the specified time to execute, so it was killed".to_string()));
-                    }
+                // checked first, the native sandbox's timeout wrapper also dies from SIGKILL
+                // when its child is OOM killed
+                if oom_killed {
+                    return Err(SimulatorError::MemoryLimitExceeded(
+                        "Process used more memory than the specified limit, so it was killed"
+                            .to_string(),
+                    ));
+                }
+                if out.status.signal() == Some(SIGKILL) {
+                    return Err(SimulatorError::TimeOutError(
+                        "Process took longer than the specified time to execute, so it was killed"
+                            .to_string(),
+                    ));
                 }
 
                 match logs_extraction_result {
-                    Err(e) => Err(SimulatorError::UnidentifiedError(
-                        format!(
-                            "Program exited with non zero exit code followed by error during log extraction: {}",
-                            e
-                        )
-                        .to_owned(),
-                    )),
+                    Err(e) => Err(SimulatorError::UnidentifiedError(format!(
+                        "Program exited with non zero exit code followed by error during log extraction: {}",
+                        e
+                    ))),
                     Ok(logs) => Err(make_err(format!(
                         "Program exited with non zero exit code: {} ",
                         logs
@@ -89,7 +114,7 @@ fn get_turnwise_logs(player_log: String) -> HashMap<usize, Vec<String>> {
             processing = true;
             match ln
                 .strip_prefix("TURN ")
-                .and_then(|x| usize::from_str_radix(x, 10).ok())
+                .and_then(|x| x.parse::<usize>().ok())
             {
                 Some(num) => cur_turn_no = num,
                 None => {
@@ -115,12 +140,93 @@ pub fn create_final_response(
     game_request: request::GameRequest,
     player_log: String,
     simulator_log: String,
+    resource_usage: Option<GameResourceUsage>,
+    log_format: LogFormat,
+) -> response::GameStatus {
+    response::GameStatus {
+        game_id: game_request.game_id,
+        game_status: GameStatusEnum::EXECUTED,
+        game_result: Some(create_game_result(
+            game_request.parameters.no_of_coins,
+            player_log,
+            &simulator_log,
+            resource_usage,
+            log_format,
+        )),
+        pvp_game_result: None,
+        error: None,
+    }
+}
+
+/// Lines of the PvP simulator's log that concern a single player are prefixed with its
+/// number (`1, COINS, 90`), the rest (`TURN, 3`) concern both. Each player's log is
+/// returned in the format of a normal game's simulator log.
+fn split_pvp_simulator_log(simulator_log: &str) -> (String, String) {
+    let mut logs = (String::new(), String::new());
+    for ln in simulator_log.lines() {
+        let ln = ln.trim();
+        if let Some(ln) = ln.strip_prefix("1, ") {
+            logs.0.push_str(ln);
+            logs.0.push('\n');
+        } else if let Some(ln) = ln.strip_prefix("2, ") {
+            logs.1.push_str(ln);
+            logs.1.push('\n');
+        } else {
+            for log in [&mut logs.0, &mut logs.1] {
+                log.push_str(ln);
+                log.push('\n');
+            }
+        }
+    }
+    logs
+}
+
+pub fn create_pvp_final_response(
+    game_request: request::PvPGameRequest,
+    player_logs: (String, String),
+    simulator_log: String,
+    resource_usage: (GameResourceUsage, GameResourceUsage),
+    log_format: LogFormat,
 ) -> response::GameStatus {
+    let no_of_coins = game_request.parameters.no_of_coins;
+    let simulator_logs = split_pvp_simulator_log(&simulator_log);
+    response::GameStatus {
+        game_id: game_request.game_id,
+        game_status: GameStatusEnum::EXECUTED,
+        game_result: None,
+        pvp_game_result: Some(PvPGameResult {
+            player1: create_game_result(
+                no_of_coins,
+                player_logs.0,
+                &simulator_logs.0,
+                Some(resource_usage.0),
+                log_format,
+            ),
+            player2: create_game_result(
+                no_of_coins,
+                player_logs.1,
+                &simulator_logs.1,
+                Some(resource_usage.1),
+                log_format,
+            ),
+        }),
+        error: None,
+    }
+}
+
+fn create_game_result(
+    no_of_coins: u32,
+    player_log: String,
+    simulator_log: &str,
+    resource_usage: Option<GameResourceUsage>,
+    log_format: LogFormat,
+) -> GameResult {
     let turnwise_logs = get_turnwise_logs(player_log);
 
     let mut final_logs = String::new();
+    let mut game_log = GameLog::default();
 
-    let mut coins_left = game_request.parameters.no_of_coins;
+    let mut coins_left = no_of_coins;
     let mut destruction_percentage = 0.0;
 
     for ln in simulator_log.lines() {
@@ -132,61 +238,83 @@ pub fn create_final_response(
         if ln.starts_with("TURN") {
             match ln
                 .strip_prefix("TURN, ")
-                .and_then(|x| usize::from_str_radix(x, 10).ok())
+                .and_then(|x| x.parse::<usize>().ok())
             {
                 Some(num) => {
-                    if turnwise_logs.contains_key(&num) {
-                        for log in turnwise_logs.get(&num).unwrap().iter() {
-                            final_logs.push_str(&format!("PRINT, {}\n", log));
-                        }
+                    let logs = turnwise_logs.get(&num).cloned().unwrap_or_default();
+                    for log in logs.iter() {
+                        final_logs.push_str(&format!("PRINT, {}\n", log));
                     }
+                    game_log.start_turn(num, logs);
                 }
-                None => {}
+                None => game_log.other(ln),
             }
             continue;
         }
 
         if ln.starts_with("DESTRUCTION") {
-            match ln
+            if let Some(x) = ln
                 .strip_prefix("DESTRUCTION, ")
-                .and_then(|s| s.strip_suffix("%"))
+                .and_then(|s| s.strip_suffix('%'))
                 .and_then(|x| x.parse::<f64>().ok())
             {
-                Some(x) => {
-                    destruction_percentage = x;
-                }
-                None => {}
+                destruction_percentage = x;
+                game_log.destruction(x, ln);
+            } else {
+                game_log.other(ln);
             }
             continue;
         }
         if ln.starts_with("COINS") {
-            match ln
+            if let Some(x) = ln
                 .strip_prefix("COINS, ")
-                .and_then(|x| usize::from_str_radix(x, 10).ok())
+                .and_then(|x| x.parse::<u32>().ok())
             {
-                Some(x) => coins_left = x as u32,
-                None => {}
+                coins_left = x;
+                game_log.coins(x, ln);
+                continue;
             }
         }
+        game_log.other(ln);
     }
 
-    response::GameStatus {
-        game_id: game_request.game_id,
-        game_status: GameStatusEnum::EXECUTED,
-        game_result: Some(GameResult {
-            destruction_percentage,
-            coins_used: (game_request.parameters.no_of_coins - coins_left) as u64,
-            has_errors: false,
-            log: final_logs,
-        }),
+    let log = match log_format {
+        LogFormat::Text => final_logs,
+        LogFormat::Json => {
+            serde_json::to_string(&game_log).expect("game log is always serializable")
+        }
+    };
+
+    GameResult {
+        destruction_percentage,
+        coins_used: (no_of_coins - coins_left) as u64,
+        has_errors: false,
+        log,
+        resource_usage,
     }
 }
 
 pub fn create_executing_response(game_request: &request::GameRequest) -> response::GameStatus {
+    create_executing_response_for_id(&game_request.game_id)
+}
+
+pub fn create_executing_response_for_id(game_id: &str) -> response::GameStatus {
     response::GameStatus {
-        game_id: game_request.game_id.to_string(),
+        game_id: game_id.to_owned(),
         game_status: GameStatusEnum::EXECUTING,
         game_result: None,
+        pvp_game_result: None,
+        error: None,
+    }
+}
+
+pub fn create_cancelled_response_for_id(game_id: &str) -> response::GameStatus {
+    response::GameStatus {
+        game_id: game_id.to_owned(),
+        game_status: GameStatusEnum::CANCELLED,
+        game_result: None,
+        pvp_game_result: None,
+        error: None,
     }
 }
 
@@ -194,7 +322,12 @@ pub fn create_error_response(
     game_request: &request::GameRequest,
     err: SimulatorError,
 ) -> response::GameStatus {
+    create_error_response_for_id(&game_request.game_id, err)
+}
+
+pub fn create_error_response_for_id(game_id: &str, err: SimulatorError) -> response::GameStatus {
     error!("Error in execution: {:?}", err);
+    let name = err.name();
     let (err_type, error) = match err {
         SimulatorError::RuntimeError(e) => ("Runtime Error!".to_owned(), e),
         SimulatorError::CompilationError(e) => ("Compilation Error!".to_owned(), e),
@@ -203,17 +336,19 @@ pub fn create_error_response(
             ("Unidentified Error. Contact the POCs!".to_owned(), e)
         }
         SimulatorError::TimeOutError(e) => ("Timeout Error!".to_owned(), e),
+        SimulatorError::MemoryLimitExceeded(e) => ("Memory Limit Exceeded!".to_owned(), e),
+        SimulatorError::InvalidRequestError(e) => ("Invalid Request!".to_owned(), e),
+        SimulatorError::ValidationError(e) => ("Validation Error!".to_owned(), e),
     };
 
     let error = error
         .lines()
-        .into_iter()
         .map(|x| format!("ERRORS, {}", x))
         .collect::<Vec<String>>()
         .join("\n");
 
     response::GameStatus {
-        game_id: game_request.game_id.clone(),
+        game_id: game_id.to_owned(),
         game_status: response::GameStatusEnum::EXECUTE_ERROR,
         game_result: Some(response::GameResult {
             destruction_percentage: 0.0,
@@ -223,17 +358,27 @@ pub fn create_error_response(
                 "ERRORS, ERROR TYPE: {}\nERRORS, ERROR LOG:\n{}\n",
                 err_type, error
             ),
+            resource_usage: None,
         }),
+        pvp_game_result: None,
+        error: Some(name),
     }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use std::process::{Command, Stdio};
+
     use crate::{
-        create_final_response, get_turnwise_logs,
+        backend::Process,
+        create_cancelled_response_for_id, create_error_response_for_id, create_final_response,
+        error::SimulatorError,
+        game_log::LogFormat,
+        get_turnwise_logs, handle_process,
         request::{GameParameters, GameRequest, Language},
         response::{GameResult, GameStatus, GameStatusEnum},
+        split_pvp_simulator_log,
     };
 
     #[test]
@@ -251,11 +396,11 @@ mod tests {
             "#;
         let mut expected_result = vec![
             (
-                1 as usize,
+                1_usize,
                 vec!["Bug is here".to_owned(), "No it's here".to_owned()],
             ),
             (
-                100 as usize,
+                100_usize,
                 vec!["Nope, it's been here the whole time".to_owned()],
             ),
         ];
@@ -301,7 +446,10 @@ mod tests {
             },
             language: Language::CPP,
             source_code: "".to_owned(),
+            files: None,
             map: vec![vec![]],
+            log_format: None,
+            limits: Default::default(),
         };
 
         let tot_coins = dummy_game_request.parameters.no_of_coins;
@@ -309,6 +457,8 @@ mod tests {
             dummy_game_request,
             player_logs.to_owned(),
             simulator_logs.to_owned(),
+            None,
+            LogFormat::Text,
         );
 
         let expected_game_status = GameStatus {
@@ -318,10 +468,100 @@ mod tests {
                 destruction_percentage: 75.0,
                 coins_used: (tot_coins - 10) as u64,
                 has_errors: false,
+                resource_usage: None,
                 log: "TURN, 1\nPRINT, Bug is here\nPRINT, No it's here\nCOINS, 100\nDESTRUCTION, 20.0%\nTURN, 3\nCOINS, 100\nDESTRUCTION, 20.0%\nTURN, 100\nPRINT, Nope, it's been here the whole time\nDESTRUCTION, 75.0%\nCOINS, 10\n".to_owned()
             }),
+            pvp_game_result: None,
+            error: None,
         };
 
         assert_eq!(expected_game_status, result);
     }
+
+    #[test]
+    fn split_pvp_simulator_log_test() {
+        let simulator_logs = r#"TURN, 1
+            1, COINS, 90
+            2, COINS, 80
+            1, DESTRUCTION, 10.0%
+            TURN, 2
+            2, DESTRUCTION, 5.0%"#;
+
+        let (player1, player2) = split_pvp_simulator_log(simulator_logs);
+        assert_eq!(player1, "TURN, 1\nCOINS, 90\nDESTRUCTION, 10.0%\nTURN, 2\n");
+        assert_eq!(player2, "TURN, 1\nCOINS, 80\nTURN, 2\nDESTRUCTION, 5.0%\n");
+    }
+
+    #[test]
+    fn create_final_response_json_test() {
+        let player_logs = "TURN 1\nBug is here\nENDLOG\n";
+        let simulator_logs = "TURN, 1\nSPAWN, 1, 0, 0\nCOINS, 90\nDESTRUCTION, 20.0%\nTURN, 2\n";
+        let dummy_game_request = GameRequest {
+            game_id: "1".to_owned(),
+            parameters: GameParameters {
+                attackers: vec![],
+                defenders: vec![],
+                no_of_turns: 500,
+                no_of_coins: 100,
+            },
+            language: Language::CPP,
+            source_code: "".to_owned(),
+            files: None,
+            map: vec![vec![]],
+            log_format: Some(LogFormat::Json),
+            limits: Default::default(),
+        };
+
+        let result = create_final_response(
+            dummy_game_request,
+            player_logs.to_owned(),
+            simulator_logs.to_owned(),
+            None,
+            LogFormat::Json,
+        )
+        .game_result
+        .unwrap();
+
+        assert_eq!(result.coins_used, 10);
+        assert_eq!(result.destruction_percentage, 20.0);
+        assert_eq!(
+            result.log,
+            r#"{"preamble":[],"turns":[{"turn":1,"prints":["Bug is here"],"coins":90,"destruction":20.0,"events":["SPAWN, 1, 0, 0"]},{"turn":2,"prints":[],"coins":null,"destruction":null,"events":[]}]}"#
+        );
+    }
+
+    fn sh(script: &str) -> Process {
+        let child = Command::new("sh")
+            .args(["-c", script])
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        Process::sandboxed(child, None, "/tmp")
+    }
+
+    #[test]
+    fn handle_process_classifies_kills() {
+        // only the cgroup or the container's state tell about OOM kills
+        let out = handle_process(sh("exit 137"), true, SimulatorError::RuntimeError);
+        assert!(matches!(out, Err(SimulatorError::RuntimeError(_))));
+
+        let out = handle_process(sh("kill -9 $$"), true, SimulatorError::RuntimeError);
+        assert!(matches!(out, Err(SimulatorError::TimeOutError(_))));
+
+        let out = handle_process(
+            sh("echo oops >&2; exit 1"),
+            true,
+            SimulatorError::RuntimeError,
+        );
+        assert!(matches!(out, Err(SimulatorError::RuntimeError(e)) if e.contains("oops")));
+    }
+
+    #[test]
+    fn error_kind_is_only_set_for_errors() {
+        let status =
+            create_error_response_for_id("1", SimulatorError::TimeOutError("slow".to_owned()));
+        assert_eq!(status.error, Some("TimeOutError"));
+        // replacing the error with a cancellation doesn't leave it to be counted
+        assert_eq!(create_cancelled_response_for_id("1").error, None);
+    }
 }
@@ -0,0 +1,257 @@
+use std::{io, sync::OnceLock, thread::JoinHandle, time::Instant};
+
+use log::{info, warn};
+use prometheus::{
+    Counter, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{
+    request::Language,
+    response::{GameStatusEnum, ResourceUsage},
+};
+
+/// Prometheus metrics of the driver, shared by every worker and transport
+pub struct Metrics {
+    registry: Registry,
+    games_received: IntCounterVec,
+    games_completed: IntCounterVec,
+    errors: IntCounterVec,
+    compile_duration: HistogramVec,
+    run_duration: HistogramVec,
+    queue_wait: Histogram,
+    workers: IntGauge,
+    busy_workers: IntGauge,
+    worker_busy_seconds: Counter,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// The driver's metrics, registered on first use
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+fn label(language: Language) -> String {
+    format!("{:?}", language)
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("driver".to_owned()), None)
+            .expect("the prefix is a valid metric name");
+        let durations = vec![0.1, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+        let metrics = Metrics {
+            games_received: IntCounterVec::new(
+                Opts::new(
+                    "games_received_total",
+                    "Games received, PvP games count once per player",
+                ),
+                &["language"],
+            )
+            .unwrap(),
+            games_completed: IntCounterVec::new(
+                Opts::new(
+                    "games_completed_total",
+                    "Games whose final status was produced, by its status",
+                ),
+                &["language", "status"],
+            )
+            .unwrap(),
+            errors: IntCounterVec::new(
+                Opts::new("errors_total", "Errors reported to players, by kind"),
+                &["error"],
+            )
+            .unwrap(),
+            compile_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "compile_duration_seconds",
+                    "Wall time of compilations, cache hits aren't counted",
+                )
+                .buckets(durations.clone()),
+                &["language"],
+            )
+            .unwrap(),
+            run_duration: HistogramVec::new(
+                HistogramOpts::new("run_duration_seconds", "Wall time of player processes")
+                    .buckets(durations),
+                &["language"],
+            )
+            .unwrap(),
+            queue_wait: Histogram::with_opts(
+                HistogramOpts::new(
+                    "queue_wait_seconds",
+                    "Time games spent waiting for a free worker",
+                )
+                .buckets(vec![0.01, 0.1, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0]),
+            )
+            .unwrap(),
+            workers: IntGauge::new("workers", "Number of worker threads").unwrap(),
+            busy_workers: IntGauge::new("busy_workers", "Workers currently running a game")
+                .unwrap(),
+            worker_busy_seconds: Counter::new(
+                "worker_busy_seconds_total",
+                "Time spent by workers running games, divide its rate by workers for utilisation",
+            )
+            .unwrap(),
+            registry,
+        };
+
+        let registry = &metrics.registry;
+        registry
+            .register(Box::new(metrics.games_received.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.games_completed.clone()))
+            .unwrap();
+        registry.register(Box::new(metrics.errors.clone())).unwrap();
+        registry
+            .register(Box::new(metrics.compile_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.run_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.queue_wait.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.workers.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.busy_workers.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.worker_busy_seconds.clone()))
+            .unwrap();
+        metrics
+    }
+
+    pub fn game_received(&self, language: Language) {
+        self.games_received
+            .with_label_values(&[&label(language)])
+            .inc();
+    }
+
+    pub fn game_completed(&self, language: Language, status: &GameStatusEnum) {
+        let status = match status {
+            GameStatusEnum::EXECUTED => "executed",
+            GameStatusEnum::EXECUTE_ERROR => "error",
+            GameStatusEnum::CANCELLED => "cancelled",
+            GameStatusEnum::IDLE | GameStatusEnum::EXECUTING => "unfinished",
+        };
+        self.games_completed
+            .with_label_values(&[&label(language), status])
+            .inc();
+    }
+
+    /// A game ended with an error, `error` is the name of the
+    /// [`SimulatorError`](crate::error::SimulatorError) variant
+    pub fn error(&self, error: &str) {
+        self.errors.with_label_values(&[error]).inc();
+    }
+
+    pub fn compiled(&self, language: Language, usage: &ResourceUsage) {
+        self.compile_duration
+            .with_label_values(&[&label(language)])
+            .observe(usage.wall_time_ms as f64 / 1000.0);
+    }
+
+    pub fn ran(&self, language: Language, usage: &ResourceUsage) {
+        self.run_duration
+            .with_label_values(&[&label(language)])
+            .observe(usage.wall_time_ms as f64 / 1000.0);
+    }
+
+    pub fn set_workers(&self, workers: usize) {
+        self.workers.set(workers as i64);
+    }
+
+    /// A worker picked up a game queued at `queued_at`
+    pub fn job_started(&self, queued_at: Instant) {
+        self.queue_wait.observe(queued_at.elapsed().as_secs_f64());
+        self.busy_workers.inc();
+    }
+
+    pub fn job_finished(&self, started: Instant) {
+        self.busy_workers.dec();
+        self.worker_busy_seconds
+            .inc_by(started.elapsed().as_secs_f64());
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn gather(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            warn!("Failed to encode metrics: {}", e);
+        }
+        buffer
+    }
+}
+
+/// Serves `GET /metrics` on its own thread
+pub fn serve(address: &str) -> io::Result<JoinHandle<()>> {
+    let server = Server::http(address).map_err(io::Error::other)?;
+    info!("Serving metrics on {:?}", server.server_addr());
+    Ok(std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.method() == &Method::Get && request.url() == "/metrics" {
+                Response::from_data(metrics().gather()).with_header(
+                    Header::from_bytes(
+                        &b"Content-Type"[..],
+                        TextEncoder::new().format_type().as_bytes(),
+                    )
+                    .expect("valid header"),
+                )
+            } else {
+                Response::from_data(b"Not found".to_vec()).with_status_code(404)
+            };
+            if let Err(e) = request.respond(response) {
+                warn!("Failed to send metrics: {}", e);
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::metrics;
+    use crate::{
+        error::SimulatorError,
+        request::Language,
+        response::{GameStatusEnum, ResourceUsage},
+    };
+
+    #[test]
+    fn metrics_are_exported() {
+        let metrics = metrics();
+        metrics.game_received(Language::PYTHON);
+        metrics.game_completed(Language::PYTHON, &GameStatusEnum::EXECUTE_ERROR);
+        metrics.error(SimulatorError::TimeOutError("".to_owned()).name());
+        metrics.compiled(
+            Language::JAVA,
+            &ResourceUsage {
+                wall_time_ms: 1500,
+                cpu_time_ms: None,
+                peak_memory_kb: None,
+            },
+        );
+        metrics.job_started(Instant::now());
+        metrics.job_finished(Instant::now());
+
+        let exported = String::from_utf8(metrics.gather()).unwrap();
+        for line in [
+            r#"driver_games_received_total{language="PYTHON"}"#,
+            r#"driver_games_completed_total{language="PYTHON",status="error"}"#,
+            r#"driver_errors_total{error="TimeOutError"}"#,
+            r#"driver_compile_duration_seconds_bucket{language="JAVA",le="2"}"#,
+            "driver_queue_wait_seconds_count",
+            "driver_busy_workers",
+        ] {
+            assert!(exported.contains(line), "{} not in {}", line, exported);
+        }
+    }
+}
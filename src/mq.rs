@@ -1,25 +1,151 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
 
-use crate::{error::SimulatorError, request::GameRequest, response::GameStatus};
+use crate::{
+    cancel::cancellations,
+    config::AmqpConfig,
+    create_error_response_for_id,
+    error::SimulatorError,
+    job::{Ack, Job, JobOutcome, StatusPublisher},
+    metrics::metrics,
+    request::{ControlRequest, DriverRequest},
+    response::GameStatus,
+    shutdown::shutdown,
+};
 use amiquip::{
-    Channel, Connection, ConsumerMessage, ConsumerOptions, Exchange, Publish, QueueDeclareOptions,
-    Result,
+    AmqpProperties, AmqpValue, Channel, Confirm, Connection, ConsumerMessage, ConsumerOptions,
+    Delivery, Exchange, ExchangeDeclareOptions, ExchangeType, FieldTable, Publish,
+    QueueDeclareOptions, Result,
 };
+use crossbeam_channel::{never, select, Receiver, Sender, TryRecvError};
+use log::{error, info};
+
+struct AckMessage {
+    generation: u64,
+    delivery_tag: u64,
+    outcome: JobOutcome,
+}
+
+/// Exponential backoff between reconnection attempts
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Backoff {
+            initial,
+            max,
+            current: initial,
+        }
+    }
+    fn from_config(amqp_config: &AmqpConfig) -> Self {
+        Backoff::new(
+            Duration::from_millis(amqp_config.reconnect_initial_delay_ms),
+            Duration::from_millis(amqp_config.reconnect_max_delay_ms),
+        )
+    }
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+/// Everything that outlives a single connection to the broker
+struct Dispatch {
+    jobs: Sender<Job>,
+    acks: Sender<AckMessage>,
+    ack_receiver: Receiver<AckMessage>,
+    publisher: Arc<Publisher>,
+}
 
-const NUM_OF_THREADS: usize = 2;
+enum ConsumerExit {
+    /// We closed the consumer ourselves, nothing to reconnect
+    Stopped,
+    /// The broker cancelled the consumer, e.g. because the queue was deleted
+    Cancelled,
+}
 
+/// Consumes requests from the request queue and hands them to the `num_of_threads`
+/// workers receiving from `jobs`, reconnecting whenever the connection is lost.
+///
+/// Returns once shutdown is requested and every game handed to a worker is done.
 pub fn consumer(
-    url: String,
-    consumer_queue_name: String,
-    response_producer_queue_name: String,
-    handler_fn: fn(crossbeam_channel::Receiver<GameRequest>, Arc<Publisher>) -> (),
+    amqp_config: &AmqpConfig,
+    num_of_threads: usize,
+    jobs: Sender<Job>,
 ) -> amiquip::Result<()> {
-    let mut connection = Connection::insecure_open(&url)?;
+    let response_publisher = Arc::new(Publisher::new(amqp_config));
+
+    let (ack_s, ack_r) = crossbeam_channel::unbounded();
+
+    let dispatch = Dispatch {
+        jobs,
+        acks: ack_s,
+        ack_receiver: ack_r,
+        publisher: response_publisher,
+    };
+
+    // never hold more unacked deliveries than we can work on
+    let prefetch_count = amqp_config
+        .prefetch_count
+        .unwrap_or(num_of_threads.min(u16::MAX as usize) as u16);
+
+    let mut backoff = Backoff::from_config(amqp_config);
+    // delivery tags are per channel, so acks from before a reconnect must be told apart
+    let mut generation = 0;
+    loop {
+        generation += 1;
+        let reason = match consume(
+            amqp_config,
+            prefetch_count,
+            generation,
+            &dispatch,
+            &mut backoff,
+        ) {
+            Ok(ConsumerExit::Stopped) => break,
+            // games still running were requeued by the broker along with the channel
+            _ if shutdown().requested() => break,
+            Ok(ConsumerExit::Cancelled) => "consumer cancelled by the server".to_owned(),
+            Err(e) => e.to_string(),
+        };
+        let delay = backoff.next_delay();
+        error!(
+            "Lost connection to the request queue ({}), reconnecting in {:?}",
+            reason, delay
+        );
+        std::thread::sleep(delay);
+    }
+    dispatch.publisher.close(CLOSE_TIMEOUT);
+    Ok(())
+}
+
+fn consume(
+    amqp_config: &AmqpConfig,
+    prefetch_count: u16,
+    generation: u64,
+    dispatch: &Dispatch,
+    backoff: &mut Backoff,
+) -> amiquip::Result<ConsumerExit> {
+    let mut connection = Connection::insecure_open(&amqp_config.url)?;
 
     let channel = connection.open_channel(None)?;
 
+    channel.qos(0, prefetch_count, false)?;
+
+    let mut dead_letters = DeadLetters::open(&mut connection, &amqp_config.dead_letter_queue)?;
+
     let queue = channel.queue_declare(
-        &consumer_queue_name,
+        &amqp_config.request_queue,
         QueueDeclareOptions {
             durable: true,
             ..Default::default()
@@ -27,55 +153,472 @@ pub fn consumer(
     )?;
 
     let consumer = queue.consume(ConsumerOptions::default())?;
+    info!("Consuming from {}", amqp_config.request_queue);
 
-    let response_publisher = Arc::new(Publisher::new(url, response_producer_queue_name).unwrap());
-
-    let (s, r) = crossbeam_channel::bounded(NUM_OF_THREADS);
-
-    // each thread has a receiver
-    let mut threads = vec![];
-    for _ in 0..NUM_OF_THREADS {
-        let new_r = r.clone();
-        let publisher_clone = Arc::clone(&response_publisher);
-        threads.push(std::thread::spawn(move || {
-            handler_fn(new_r, publisher_clone)
-        }))
-    }
-
-    for message in consumer.receiver().iter() {
-        match message {
-            ConsumerMessage::Delivery(delivery) => {
-                let body_str = String::from_utf8_lossy(&delivery.body);
-                let res: Result<GameRequest, serde_json::Error> = serde_json::from_str(&body_str);
-                match res {
-                    Ok(match_request) => {
-                        s.send(match_request).unwrap();
-                        consumer.ack(delivery)?;
+    let control_exchange = channel.exchange_declare(
+        ExchangeType::Fanout,
+        &amqp_config.control_exchange,
+        ExchangeDeclareOptions {
+            durable: true,
+            ..Default::default()
+        },
+    )?;
+    // named by the broker and deleted along with the connection
+    let control_queue = channel.queue_declare(
+        "",
+        QueueDeclareOptions {
+            exclusive: true,
+            ..Default::default()
+        },
+    )?;
+    control_queue.bind(&control_exchange, "", FieldTable::new())?;
+    let control = control_queue.consume(ConsumerOptions {
+        no_ack: true,
+        ..Default::default()
+    })?;
+    backoff.reset();
+
+    // deliveries handed to a worker, waiting for the game to finish
+    let mut in_flight: HashMap<u64, Delivery> = HashMap::new();
+
+    // once stopped the consumer is cancelled, deliveries that were already sent to us
+    // are requeued when the connection is closed
+    let stopped = shutdown().stopped();
+    let mut stopping = false;
+    let no_deliveries = never();
+    let no_stop = never();
+
+    let exit = loop {
+        if stopping && in_flight.is_empty() {
+            break ConsumerExit::Stopped;
+        }
+        select! {
+            recv(if stopping { &no_stop } else { &stopped }) -> _ => {
+                info!(
+                    "Stopped consuming from {}, waiting for {} games",
+                    amqp_config.request_queue,
+                    in_flight.len()
+                );
+                consumer.cancel()?;
+                stopping = true;
+            }
+            recv(if stopping { &no_deliveries } else { consumer.receiver() }) -> message => match message {
+                Ok(ConsumerMessage::Delivery(delivery)) if shutdown().requested() => {
+                    consumer.nack(delivery, true)?;
+                }
+                Ok(ConsumerMessage::Delivery(delivery)) => {
+                    let body_str = String::from_utf8_lossy(&delivery.body);
+                    match DriverRequest::from_json(&body_str) {
+                        Ok(match_request) => {
+                            let delivery_tag = delivery.delivery_tag();
+                            in_flight.insert(delivery_tag, delivery);
+                            let acks = dispatch.acks.clone();
+                            dispatch
+                                .jobs
+                                .send(Job::new(
+                                    match_request,
+                                    // the consumer only goes away when the driver is shutting
+                                    // down, the delivery will be redelivered by the broker
+                                    Ack::new(move |outcome| {
+                                        let _ = acks.send(AckMessage {
+                                            generation,
+                                            delivery_tag,
+                                            outcome,
+                                        });
+                                    }),
+                                    dispatch.publisher.clone(),
+                                ))
+                                .unwrap();
+                        }
+                        Err(e) => {
+                            error!("Malformed game request: {}", e);
+                            // rejected once the copy is safe, until then it stays queued
+                            if !dead_letters.publish(&delivery, &e.to_string()) {
+                                consumer.nack(delivery, true)?;
+                                continue;
+                            }
+                            if let Some(game_id) = salvage_game_id(&body_str) {
+                                let status = create_error_response_for_id(
+                                    &game_id,
+                                    SimulatorError::InvalidRequestError(format!(
+                                        "Malformed game request: {}",
+                                        e
+                                    )),
+                                );
+                                if let Some(error) = status.error {
+                                    metrics().error(error);
+                                }
+                                if let Err(e) = dispatch.publisher.publish(status) {
+                                    error!("Failed to publish status for {}: {:?}", game_id, e);
+                                }
+                            }
+                            consumer.reject(delivery, false)?;
+                        }
                     }
-                    Err(e) => {
-                        eprintln!("{:?}", e);
+                }
+                Ok(ConsumerMessage::ServerClosedChannel(e))
+                | Ok(ConsumerMessage::ServerClosedConnection(e)) => return Err(e),
+                Ok(ConsumerMessage::ServerCancelled) => break ConsumerExit::Cancelled,
+                other => {
+                    info!("Consumer ended: {:?}", other);
+                    break ConsumerExit::Stopped;
+                }
+            },
+            recv(control.receiver()) -> message => match message {
+                Ok(ConsumerMessage::Delivery(delivery)) => handle_control(&delivery.body),
+                Ok(ConsumerMessage::ServerClosedChannel(e))
+                | Ok(ConsumerMessage::ServerClosedConnection(e)) => return Err(e),
+                other => {
+                    info!("Control consumer ended: {:?}", other);
+                    break ConsumerExit::Cancelled;
+                }
+            },
+            recv(dispatch.ack_receiver) -> message => {
+                let message = message.unwrap();
+                if message.generation != generation {
+                    // the broker already requeued it when the old channel went away
+                    continue;
+                }
+                if let Some(delivery) = in_flight.remove(&message.delivery_tag) {
+                    match message.outcome {
+                        JobOutcome::Completed => consumer.ack(delivery)?,
+                        JobOutcome::Failed => {
+                            // requeue only once, so that a request which keeps breaking the
+                            // driver doesn't loop forever
+                            let requeue = !delivery.redelivered;
+                            error!(
+                                "Game failed internally, {}",
+                                if requeue { "requeueing" } else { "dropping" }
+                            );
+                            consumer.nack(delivery, requeue)?;
+                        }
+                        JobOutcome::NotStarted => consumer.nack(delivery, true)?,
                     }
                 }
             }
-            other => {
-                println!("Consumer ended: {:?}", other);
-                break;
+        }
+    };
+
+    connection.close()?;
+    Ok(exit)
+}
+
+/// Applies a message from the control exchange, games that aren't queued or running on
+/// this driver are left to the other drivers
+fn handle_control(body: &[u8]) {
+    match serde_json::from_slice::<ControlRequest>(body) {
+        Ok(ControlRequest::Cancel { game_id }) => {
+            if !cancellations().cancel(&game_id) {
+                info!("Game {} to cancel isn't on this driver", game_id);
             }
         }
+        Err(e) => error!("Malformed control message: {}", e),
     }
+}
 
-    connection.close()
+/// Header carrying the reason a request was dead lettered
+const DEAD_LETTER_REASON_HEADER: &str = "x-driver-error";
+/// How long the broker has to confirm a dead lettered request
+const DEAD_LETTER_CONFIRM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Channel with publisher confirms the malformed requests are copied to the dead letter
+/// queue on, so that a request is only rejected once the broker has its copy
+struct DeadLetters {
+    channel: Channel,
+    queue_name: String,
+    confirms: Receiver<Confirm>,
+    /// Delivery tag of the last copy, they start at 1 once confirms are enabled
+    last_tag: u64,
 }
 
-pub struct Publisher {
-    connection: Option<Connection>,
-    channel: Mutex<Channel>,
+impl DeadLetters {
+    fn open(connection: &mut Connection, queue_name: &str) -> Result<Self> {
+        let channel = connection.open_channel(None)?;
+        let confirms = channel.listen_for_publisher_confirms()?;
+        channel.enable_publisher_confirms()?;
+        channel.queue_declare(
+            queue_name,
+            QueueDeclareOptions {
+                durable: true,
+                ..Default::default()
+            },
+        )?;
+        Ok(DeadLetters {
+            channel,
+            queue_name: queue_name.to_owned(),
+            confirms,
+            last_tag: 0,
+        })
+    }
+
+    /// Whether the broker confirmed the copy, failures are logged
+    fn publish(&mut self, delivery: &Delivery, reason: &str) -> bool {
+        if let Err(e) = dead_letter(&self.channel, &self.queue_name, delivery, reason) {
+            error!("Failed to dead letter a request: {}", e);
+            return false;
+        }
+        self.last_tag += 1;
+        loop {
+            let (payload, acked) = match self.confirms.recv_timeout(DEAD_LETTER_CONFIRM_TIMEOUT) {
+                Ok(Confirm::Ack(payload)) => (payload, true),
+                Ok(Confirm::Nack(payload)) => (payload, false),
+                Err(e) => {
+                    error!("The broker didn't confirm a dead lettered request: {}", e);
+                    return false;
+                }
+            };
+            // confirms of copies that timed out before can still arrive
+            let covered = payload.delivery_tag == self.last_tag
+                || (payload.multiple && payload.delivery_tag > self.last_tag);
+            if covered {
+                if !acked {
+                    error!("The broker rejected a dead lettered request");
+                }
+                return acked;
+            }
+        }
+    }
+}
+
+/// Copies the delivery into the dead letter queue, with the error attached as a header
+fn dead_letter(
+    channel: &Channel,
+    queue_name: &str,
+    delivery: &Delivery,
+    reason: &str,
+) -> Result<()> {
+    let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+    headers.insert(
+        DEAD_LETTER_REASON_HEADER.to_owned(),
+        AmqpValue::LongString(reason.to_owned()),
+    );
+    let properties = delivery
+        .properties
+        .clone()
+        .with_headers(headers)
+        .with_delivery_mode(2);
+    Exchange::direct(channel).publish(Publish::with_properties(
+        &delivery.body,
+        queue_name,
+        properties,
+    ))
+}
+
+/// Tries to pull the game id out of a request that couldn't be deserialized,
+/// so that the backend can still be told the game failed. The body doesn't have to be
+/// valid JSON, it's scanned for a `"game_id": "<id>"` pair when it doesn't parse.
+fn salvage_game_id(body: &str) -> Option<String> {
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(body) {
+        return value.get("game_id")?.as_str().map(|id| id.to_owned());
+    }
+    const KEY: &str = "\"game_id\"";
+    body.match_indices(KEY).find_map(|(start, _)| {
+        let value = body[start + KEY.len()..]
+            .trim_start()
+            .strip_prefix(':')?
+            .trim_start();
+        if !value.starts_with('"') {
+            return None;
+        }
+        // the string ends at the first quote that isn't escaped
+        let mut escaped = false;
+        let end = value.char_indices().skip(1).find_map(|(i, c)| match c {
+            '"' if !escaped => Some(i),
+            '\\' => {
+                escaped = !escaped;
+                None
+            }
+            _ => {
+                escaped = false;
+                None
+            }
+        })?;
+        serde_json::from_str::<String>(&value[..=end]).ok()
+    })
+}
+
+/// A status waiting to be confirmed by the broker
+struct PendingStatus {
+    body: String,
+    /// Completed once the broker confirms the status, only final statuses carry one
+    ack: Option<Ack>,
+}
+
+struct PublisherConnection {
+    connection: Connection,
+    channel: Channel,
+    confirms: Receiver<Confirm>,
+    /// Statuses sent on this channel by delivery tag, resent if the channel goes away
+    /// before they are confirmed
+    unconfirmed: BTreeMap<u64, PendingStatus>,
+    next_tag: u64,
+}
+
+struct PublisherState {
+    connection: Option<PublisherConnection>,
+    /// Serialized statuses waiting for the broker to come back
+    pending: VecDeque<PendingStatus>,
+    backoff: Backoff,
+    next_attempt: Instant,
+}
+
+impl PublisherState {
+    fn buffered(&self) -> usize {
+        self.pending.len()
+            + self
+                .connection
+                .as_ref()
+                .map_or(0, |connection| connection.unconfirmed.len())
+    }
+}
+
+struct PublisherInner {
+    url: String,
     queue_name: String,
+    max_pending: usize,
+    state: Mutex<PublisherState>,
+    /// Notified when buffered statuses are confirmed
+    space: Condvar,
 }
 
+/// Publishes status updates with publisher confirms, reconnecting to the broker in the
+/// background when the connection is lost. Updates are buffered in memory until the
+/// broker confirms them, publishing blocks while the buffer is full.
+pub struct Publisher {
+    inner: Arc<PublisherInner>,
+}
+
+/// How long the statuses still buffered on shutdown are waited on
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the background thread checks for confirms and whether buffered statuses
+/// can be flushed
+const FLUSH_INTERVAL: Duration = Duration::from_millis(200);
+
 impl Publisher {
-    pub fn new(url: String, queue_name: String) -> Result<Self, SimulatorError> {
-        let mut connection = Connection::insecure_open(&url).map_err(|e| {
+    pub fn new(amqp_config: &AmqpConfig) -> Self {
+        let inner = Arc::new(PublisherInner {
+            url: amqp_config.url.clone(),
+            queue_name: amqp_config.status_queue.clone(),
+            max_pending: amqp_config.max_pending_statuses.max(1),
+            state: Mutex::new(PublisherState {
+                connection: None,
+                pending: VecDeque::new(),
+                backoff: Backoff::from_config(amqp_config),
+                next_attempt: Instant::now(),
+            }),
+            space: Condvar::new(),
+        });
+
+        if let Err(e) = inner.connect(&mut inner.state.lock().unwrap()) {
+            error!("{:?}", e);
+        }
+
+        let weak = Arc::downgrade(&inner);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(FLUSH_INTERVAL);
+            match weak.upgrade() {
+                Some(inner) => inner.flush_and_ack(inner.state.lock().unwrap()),
+                None => break,
+            }
+        });
+
+        Self { inner }
+    }
+
+    /// Sends the pending statuses if the broker is reachable and closes the connection
+    /// once they are confirmed, or after `timeout`
+    pub fn close(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.inner.state.lock().unwrap();
+        state.next_attempt = Instant::now();
+        self.inner.flush_and_ack(state);
+        // the background thread keeps flushing in the meantime
+        let mut state = self.inner.state.lock().unwrap();
+        while state.buffered() > 0 && Instant::now() < deadline {
+            state = self
+                .inner
+                .space
+                .wait_timeout(state, FLUSH_INTERVAL)
+                .unwrap()
+                .0;
+        }
+        if let Some(PublisherConnection { connection, .. }) = state.connection.take() {
+            let _ = connection.close();
+        }
+    }
+
+    /// Buffers the status, waiting for the broker while the buffer is full. The status is
+    /// handed back if the driver is killed in the meantime.
+    fn enqueue(&self, status: PendingStatus) -> Result<(), PendingStatus> {
+        let mut state = self.inner.state.lock().unwrap();
+        // nothing is dropped, the worker waits for the broker instead
+        while state.buffered() >= self.inner.max_pending {
+            if shutdown().killed() {
+                return Err(status);
+            }
+            // the background thread keeps flushing in the meantime
+            state = self
+                .inner
+                .space
+                .wait_timeout(state, FLUSH_INTERVAL)
+                .unwrap()
+                .0;
+        }
+        state.pending.push_back(status);
+        self.inner.flush_and_ack(state);
+        Ok(())
+    }
+}
+
+fn serialize(response: &GameStatus) -> Result<String, SimulatorError> {
+    serde_json::to_string(response).map_err(|e| SimulatorError::UnidentifiedError(format!("{}", e)))
+}
+
+/// Error for statuses that couldn't be buffered
+fn buffer_full() -> SimulatorError {
+    SimulatorError::UnidentifiedError("Too many status updates waiting for the broker".to_owned())
+}
+
+impl StatusPublisher for Publisher {
+    /// Queues the status for publishing and tries to send everything that is pending.
+    ///
+    /// Fails if the status can't be serialized, or if the driver is shutting down while
+    /// the buffer is full. Statuses that can't be sent right away are retried once the
+    /// connection is back.
+    fn publish(&self, response: GameStatus) -> Result<(), SimulatorError> {
+        let body = serialize(&response)?;
+        self.enqueue(PendingStatus { body, ack: None })
+            .map_err(|_| buffer_full())
+    }
+
+    /// The request is acked once the broker confirms the status
+    fn publish_final(&self, response: GameStatus, ack: Ack) {
+        let body = match serialize(&response) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Failed to publish result for {}: {:?}", response.game_id, e);
+                return ack.complete(JobOutcome::Failed);
+            }
+        };
+        if let Err(status) = self.enqueue(PendingStatus {
+            body,
+            ack: Some(ack),
+        }) {
+            error!(
+                "Failed to publish result for {}: {:?}",
+                response.game_id,
+                buffer_full()
+            );
+            if let Some(ack) = status.ack {
+                ack.complete(JobOutcome::Failed);
+            }
+        }
+    }
+}
+
+impl PublisherInner {
+    fn connect(&self, state: &mut PublisherState) -> Result<(), SimulatorError> {
+        let mut connection = Connection::insecure_open(&self.url).map_err(|e| {
             SimulatorError::UnidentifiedError(format!(
                 "Error in opening connection to publish queue [Connection::insecure_open]: {}",
                 e
@@ -91,7 +634,7 @@ impl Publisher {
 
         channel
             .queue_declare(
-                &queue_name,
+                &self.queue_name,
                 QueueDeclareOptions {
                     durable: true,
                     ..Default::default()
@@ -104,34 +647,198 @@ impl Publisher {
                 ))
             })?;
 
-        Ok(Self {
-            connection: Some(connection),
-            channel: Mutex::new(channel),
-            queue_name,
-        })
-    }
-    pub fn publish(&self, response: GameStatus) -> Result<(), SimulatorError> {
-        let channel = self.channel.lock().unwrap();
-        let exchange = Exchange::direct(&channel);
-        let body = serde_json::to_string(&response)
-            .map_err(|e| SimulatorError::UnidentifiedError(format!("{}", e)))?;
-        exchange
-            .publish(Publish::new(&body.as_bytes(), &self.queue_name))
+        let confirms = channel
+            .listen_for_publisher_confirms()
+            .and_then(|confirms| channel.enable_publisher_confirms().map(|_| confirms))
             .map_err(|e| {
                 SimulatorError::UnidentifiedError(format!(
-                    "Error in publishing to the queue[Publisher::publish]{}",
+                    "Error in enabling publisher confirms [Publisher::new]: {}",
                     e
                 ))
             })?;
+
+        state.connection = Some(PublisherConnection {
+            connection,
+            channel,
+            confirms,
+            unconfirmed: BTreeMap::new(),
+            // delivery tags start at 1 once confirms are enabled
+            next_tag: 1,
+        });
+        state.backoff.reset();
         Ok(())
     }
+
+    fn disconnect(&self, state: &mut PublisherState) {
+        if let Some(PublisherConnection {
+            connection,
+            unconfirmed,
+            ..
+        }) = state.connection.take()
+        {
+            let _ = connection.close();
+            // the broker may not have them, they go out again before the newer ones
+            for (_, status) in unconfirmed.into_iter().rev() {
+                state.pending.push_front(status);
+            }
+        }
+        let delay = state.backoff.next_delay();
+        state.next_attempt = Instant::now() + delay;
+        error!(
+            "Lost connection to the status queue, {} updates pending, reconnecting in {:?}",
+            state.pending.len(),
+            delay
+        );
+    }
+
+    /// Flushes the statuses and completes the acks of the confirmed ones once the lock
+    /// is released, they call back into the consumer
+    fn flush_and_ack(&self, mut state: MutexGuard<PublisherState>) {
+        let confirmed = self.flush(&mut state);
+        drop(state);
+        self.space.notify_all();
+        for ack in confirmed {
+            ack.complete(JobOutcome::Completed);
+        }
+    }
+
+    /// Sends the pending statuses and collects the confirms, returns the acks of the
+    /// confirmed statuses
+    fn flush(&self, state: &mut PublisherState) -> Vec<Ack> {
+        let mut confirmed = vec![];
+        if let Some(connection) = state.connection.as_mut() {
+            let mut nacked = false;
+            loop {
+                let (payload, acked) = match connection.confirms.try_recv() {
+                    Ok(Confirm::Ack(payload)) => (payload, true),
+                    Ok(Confirm::Nack(payload)) => (payload, false),
+                    Err(TryRecvError::Empty) => break,
+                    // the connection is gone, the unconfirmed statuses are sent again
+                    Err(TryRecvError::Disconnected) => {
+                        self.disconnect(state);
+                        return confirmed;
+                    }
+                };
+                let tags = if payload.multiple {
+                    connection
+                        .unconfirmed
+                        .range(..=payload.delivery_tag)
+                        .map(|(tag, _)| *tag)
+                        .collect()
+                } else {
+                    vec![payload.delivery_tag]
+                };
+                for tag in tags {
+                    match connection.unconfirmed.remove(&tag) {
+                        Some(status) if acked => confirmed.extend(status.ack),
+                        Some(status) => {
+                            state.pending.push_back(status);
+                            nacked = true;
+                        }
+                        None => {}
+                    }
+                }
+            }
+            if nacked {
+                error!("The broker rejected status updates, sending them again");
+            }
+        }
+
+        if state.pending.is_empty() {
+            return confirmed;
+        }
+        if state.connection.is_none() {
+            if Instant::now() < state.next_attempt {
+                return confirmed;
+            }
+            if let Err(e) = self.connect(state) {
+                error!("{:?}", e);
+                self.disconnect(state);
+                return confirmed;
+            }
+            info!("Reconnected to the status queue");
+        }
+
+        while let Some(status) = state.pending.pop_front() {
+            let connection = match state.connection.as_mut() {
+                Some(connection) => connection,
+                None => {
+                    state.pending.push_front(status);
+                    return confirmed;
+                }
+            };
+            let result = Exchange::direct(&connection.channel).publish(Publish::with_properties(
+                status.body.as_bytes(),
+                &self.queue_name,
+                AmqpProperties::default().with_delivery_mode(2),
+            ));
+            match result {
+                Ok(_) => {
+                    connection.unconfirmed.insert(connection.next_tag, status);
+                    connection.next_tag += 1;
+                }
+                Err(e) => {
+                    error!("Error in publishing to the queue[Publisher::publish]{}", e);
+                    state.pending.push_front(status);
+                    self.disconnect(state);
+                    return confirmed;
+                }
+            }
+        }
+        confirmed
+    }
 }
 
-impl Drop for Publisher {
+impl Drop for PublisherInner {
     fn drop(&mut self) {
-        if self.connection.is_some() {
-            let conn = self.connection.take().unwrap();
-            let _ = conn.close();
+        let state = self.state.get_mut().unwrap();
+        let buffered = state.buffered();
+        if buffered > 0 {
+            error!(
+                "Dropping {} status updates that were never confirmed",
+                buffered
+            );
         }
+        if let Some(PublisherConnection { connection, .. }) = state.connection.take() {
+            let _ = connection.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{salvage_game_id, Backoff};
+
+    #[test]
+    fn backoff_doubles_up_to_max_and_resets() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(500));
+        let delays = (0..5)
+            .map(|_| backoff.next_delay().as_millis())
+            .collect::<Vec<u128>>();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn game_id_is_salvaged_from_malformed_requests() {
+        assert_eq!(
+            salvage_game_id(r#"{"game_id":"0fa0f12d","parameters":{},"language":"KOTLIN"}"#),
+            Some("0fa0f12d".to_owned())
+        );
+        assert_eq!(salvage_game_id(r#"{"game_id":12}"#), None);
+        assert_eq!(
+            salvage_game_id(r#"{"game_id":"0fa0f12d""#),
+            Some("0fa0f12d".to_owned())
+        );
+        assert_eq!(
+            salvage_game_id(r#"{"map":[[0,1]], "game_id" : "a\"b", "source_code":"trunc"#),
+            Some("a\"b".to_owned())
+        );
+        assert_eq!(salvage_game_id(r#"{"game_id":"0fa0"#), None);
+        assert_eq!(salvage_game_id("not json"), None);
     }
 }
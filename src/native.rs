@@ -0,0 +1,889 @@
+use std::{
+    ffi::CString,
+    fs::{self, File},
+    io,
+    os::unix::{ffi::OsStringExt, io::AsRawFd, process::CommandExt},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use nix::{
+    dir::Dir,
+    fcntl::{AtFlags, OFlag},
+    libc::{self, sock_filter, sock_fprog},
+    sched::{unshare, CloneFlags},
+    sys::{
+        resource::{setrlimit, Resource},
+        stat::{fstatat, Mode},
+    },
+    unistd::{fchown, fchownat, setgid, setuid, FchownatFlags, Gid, Uid},
+};
+
+use crate::{
+    backend::{ExecutionBackend, Process, ProcessSpec},
+    config::NativeConfig,
+};
+
+/// Period used for the cgroup cpu quota, in microseconds
+const CPU_PERIOD: u64 = 100000;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc00000b7;
+
+/// Syscalls player code has no business making, they fail with EPERM
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_socket,
+    libc::SYS_io_uring_setup,
+    libc::SYS_io_uring_enter,
+    libc::SYS_io_uring_register,
+    libc::SYS_fsopen,
+    libc::SYS_fsconfig,
+    libc::SYS_fsmount,
+    libc::SYS_fspick,
+    libc::SYS_move_mount,
+    libc::SYS_open_tree,
+    libc::SYS_name_to_handle_at,
+    libc::SYS_acct,
+    libc::SYS_quotactl,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_clock_adjtime,
+    libc::SYS_adjtimex,
+    libc::SYS_vhangup,
+    libc::SYS_personality,
+    libc::SYS_ptrace,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_reboot,
+    libc::SYS_kexec_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_setns,
+    libc::SYS_unshare,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_userfaultfd,
+];
+
+/// Flags that make `clone` create namespaces, user namespaces would hand out capabilities
+const CLONE_NAMESPACE_FLAGS: u32 = (libc::CLONE_NEWNS
+    | libc::CLONE_NEWCGROUP
+    | libc::CLONE_NEWUTS
+    | libc::CLONE_NEWIPC
+    | libc::CLONE_NEWUSER
+    | libc::CLONE_NEWPID
+    | libc::CLONE_NEWNET) as u32;
+
+/// Where the game directory is mounted in the sandbox, processes start in it
+const SANDBOX_GAME_DIR: &str = "game";
+/// Size limited tmpfs in the sandbox's root that is writable by everyone
+const SANDBOX_TMP: &str = "tmp";
+
+/// cgroup v2 group holding a single sandboxed process, removed once dropped
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    fn create(root: &str, memory_limit: u64, cpus: &str, max_processes: u64) -> io::Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let path = PathBuf::from(root).join(format!(
+            "sandbox-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir(&path)?;
+        let cgroup = Cgroup { path };
+
+        let memory = (memory_limit * 1024 * 1024).to_string();
+        let cpus = cpus
+            .parse::<f64>()
+            .map_err(|e| io::Error::other(format!("Invalid cpu limit {}: {}", cpus, e)))?;
+        cgroup.write("memory.max", &memory)?;
+        cgroup.write("memory.swap.max", "0")?;
+        cgroup.write(
+            "cpu.max",
+            &format!("{} {}", (cpus * CPU_PERIOD as f64) as u64, CPU_PERIOD),
+        )?;
+        cgroup.write("pids.max", &max_processes.to_string())?;
+        Ok(cgroup)
+    }
+
+    fn write(&self, file: &str, value: &str) -> io::Result<()> {
+        std::fs::write(self.path.join(file), value).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Failed to write {}/{}: {}", self.path.display(), file, e),
+            )
+        })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Whether the OOM killer was invoked for any process in the group
+    pub fn oom_killed(&self) -> bool {
+        std::fs::read_to_string(self.path.join("memory.events"))
+            .map(|events| parse_oom_kills(&events) > 0)
+            .unwrap_or(false)
+    }
+
+    pub fn cpu_time_ms(&self) -> Option<u64> {
+        cgroup_cpu_time_ms(&self.path)
+    }
+
+    /// Only available on kernels which have `memory.peak` (5.19+)
+    pub fn peak_memory_kb(&self) -> Option<u64> {
+        cgroup_memory_kb(&self.path, "memory.peak")
+    }
+}
+
+/// cpu time used by the processes of any cgroup (v2), including the exited ones
+pub fn cgroup_cpu_time_ms(path: &Path) -> Option<u64> {
+    let stat = std::fs::read_to_string(path.join("cpu.stat")).ok()?;
+    stat.lines()
+        .filter_map(|line| line.strip_prefix("usage_usec "))
+        .filter_map(|usec| usec.trim().parse::<u64>().ok())
+        .next()
+        .map(|usec| usec / 1000)
+}
+
+/// Reads a memory file of a cgroup (v2) holding a number of bytes, like `memory.current`
+pub fn cgroup_memory_kb(path: &Path, file: &str) -> Option<u64> {
+    std::fs::read_to_string(path.join(file))
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|bytes| bytes / 1024)
+}
+
+fn parse_oom_kills(events: &str) -> u64 {
+    events
+        .lines()
+        .filter_map(|line| line.strip_prefix("oom_kill "))
+        .filter_map(|count| count.trim().parse::<u64>().ok())
+        .next()
+        .unwrap_or(0)
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir(&self.path);
+    }
+}
+
+/// Runs processes directly on the host, isolated with namespaces, a cgroup, rlimits and a
+/// seccomp filter. The toolchains and the simulator have to be installed on the host.
+pub struct NativeBackend {
+    config: NativeConfig,
+}
+
+impl NativeBackend {
+    pub fn new(config: NativeConfig) -> Self {
+        NativeBackend { config }
+    }
+}
+
+impl ExecutionBackend for NativeBackend {
+    fn spawn(&self, spec: &ProcessSpec, stdin: Stdio, stdout: Stdio) -> io::Result<Process> {
+        if spec.command.is_empty() {
+            return Err(io::Error::other("No native command configured"));
+        }
+        if self.config.uid == 0 || self.config.gid == 0 {
+            return Err(io::Error::other(
+                "Refusing to run a process as root, set native.uid and native.gid",
+            ));
+        }
+        // compilers write their output next to the code
+        if Uid::effective().is_root() {
+            chown_all(Path::new(spec.game_dir), self.config.uid, self.config.gid)?;
+        }
+        let root = if self.config.unshare_namespaces {
+            Some(SandboxRoot::prepare(
+                spec.game_dir,
+                &self.config.read_only_paths,
+                self.config.max_file_size * 1024 * 1024,
+            )?)
+        } else {
+            None
+        };
+
+        let cgroup = if self.config.use_cgroups {
+            Some(Cgroup::create(
+                &self.config.cgroup_root,
+                spec.memory_limit,
+                spec.cpus,
+                self.config.max_processes,
+            )?)
+        } else {
+            None
+        };
+
+        // everything the child needs is prepared here, allocating after fork isn't safe
+        let cgroup_procs = cgroup
+            .as_ref()
+            .map(|cgroup| {
+                CString::new(
+                    cgroup
+                        .path()
+                        .join("cgroup.procs")
+                        .into_os_string()
+                        .into_vec(),
+                )
+            })
+            .transpose()
+            .map_err(io::Error::other)?;
+        let filter = seccomp_filter();
+        let sandbox = Sandbox {
+            cgroup_procs,
+            max_open_files: self.config.max_open_files,
+            max_file_size: self.config.max_file_size * 1024 * 1024,
+            unshare_namespaces: self.config.unshare_namespaces,
+            root,
+            drop_groups: Uid::effective().is_root(),
+            uid: self.config.uid,
+            gid: self.config.gid,
+        };
+
+        let mut command = Command::new("timeout");
+        command
+            .arg("--signal=KILL")
+            .arg(spec.time_limit.to_string())
+            .args(&spec.command)
+            .current_dir(spec.game_dir)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(Stdio::piped());
+        unsafe {
+            command.pre_exec(move || sandbox.enter(&filter));
+        }
+        let child = command.spawn()?;
+        Ok(Process::sandboxed(child, cgroup, spec.game_dir))
+    }
+
+    /// The host's toolchain versions aren't tracked, only the command used
+    fn toolchain_id(&self, _image: &str, command: &[String]) -> Option<String> {
+        Some(command.join(" "))
+    }
+}
+
+/// Restrictions applied in the forked child before exec
+struct Sandbox {
+    cgroup_procs: Option<CString>,
+    max_open_files: u64,
+    max_file_size: u64,
+    unshare_namespaces: bool,
+    root: Option<SandboxRoot>,
+    /// Supplementary groups can only be dropped by root
+    drop_groups: bool,
+    uid: u32,
+    gid: u32,
+}
+
+impl Sandbox {
+    fn enter(&self, filter: &[sock_filter]) -> io::Result<()> {
+        if let Some(procs) = &self.cgroup_procs {
+            join_cgroup(procs)?;
+        }
+
+        limit(Resource::RLIMIT_NOFILE, self.max_open_files)?;
+        limit(Resource::RLIMIT_FSIZE, self.max_file_size)?;
+        limit(Resource::RLIMIT_CORE, 0)?;
+
+        if self.unshare_namespaces {
+            unshare(
+                CloneFlags::CLONE_NEWNET
+                    | CloneFlags::CLONE_NEWIPC
+                    | CloneFlags::CLONE_NEWUTS
+                    | CloneFlags::CLONE_NEWNS,
+            )
+            .map_err(errno_to_io)?;
+        }
+        if let Some(root) = &self.root {
+            root.enter()?;
+        }
+
+        if self.drop_groups && unsafe { libc::setgroups(0, std::ptr::null()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        setgid(Gid::from_raw(self.gid)).map_err(errno_to_io)?;
+        setuid(Uid::from_raw(self.uid)).map_err(errno_to_io)?;
+
+        install_seccomp(filter)
+    }
+}
+
+/// The root of the sandbox is a read-only tmpfs mounted over the game directory, with
+/// the game directory itself at [`SANDBOX_GAME_DIR`] and the host paths the toolchains
+/// need mounted read-only at the same place. Nothing is created on the host, the player
+/// owns the game directory and could plant symlinks in it.
+struct SandboxRoot {
+    /// Reserves a descriptor the child opens the game directory at, in its own mount
+    /// namespace, so that it can be bind mounted once the tmpfs hides its path
+    game_dir: File,
+    game_dir_fd: CString,
+    root: CString,
+    game_mount: CString,
+    workdir: CString,
+    tmp: CString,
+    tmp_options: CString,
+    /// Mount points to create in the tmpfs, parents come first
+    dirs: Vec<CString>,
+    files: Vec<CString>,
+    /// Host path and where it is mounted, both absolute
+    mounts: Vec<(CString, CString)>,
+}
+
+impl SandboxRoot {
+    /// Works out the paths up front, the child can't allocate
+    fn prepare(game_dir: &str, read_only_paths: &[String], tmp_size: u64) -> io::Result<Self> {
+        let root = fs::canonicalize(game_dir)?;
+        let game_dir = File::open(&root)?;
+        let game_dir_fd = format!("/proc/self/fd/{}", game_dir.as_raw_fd());
+
+        let mut dirs = vec![];
+        let mut files = vec![];
+        let mut mounts = vec![];
+        let add_dir = |dirs: &mut Vec<CString>, path: PathBuf| -> io::Result<()> {
+            let path = c_path(&path)?;
+            if !dirs.contains(&path) {
+                dirs.push(path);
+            }
+            Ok(())
+        };
+        add_dir(&mut dirs, root.join(SANDBOX_GAME_DIR))?;
+        add_dir(&mut dirs, root.join(SANDBOX_TMP))?;
+        for path in read_only_paths {
+            let source = Path::new(path);
+            let metadata = match fs::metadata(source) {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let relative = source.strip_prefix("/").unwrap_or(source);
+            for parent in relative
+                .ancestors()
+                .skip(1)
+                .collect::<Vec<_>>()
+                .iter()
+                .rev()
+            {
+                if !parent.as_os_str().is_empty() {
+                    add_dir(&mut dirs, root.join(parent))?;
+                }
+            }
+            let target = root.join(relative);
+            if metadata.is_dir() {
+                add_dir(&mut dirs, target.clone())?;
+            } else {
+                files.push(c_path(&target)?);
+            }
+            mounts.push((c_path(source)?, c_path(&target)?));
+        }
+        Ok(SandboxRoot {
+            game_dir,
+            workdir: CString::new(format!("/{}", SANDBOX_GAME_DIR)).map_err(io::Error::other)?,
+            game_dir_fd: CString::new(game_dir_fd).map_err(io::Error::other)?,
+            game_mount: c_path(&root.join(SANDBOX_GAME_DIR))?,
+            tmp: c_path(&root.join(SANDBOX_TMP))?,
+            tmp_options: CString::new(format!("mode=1777,size={}", tmp_size))
+                .map_err(io::Error::other)?,
+            root: c_path(&root)?,
+            dirs,
+            files,
+            mounts,
+        })
+    }
+
+    /// Has to run in a new mount namespace
+    fn enter(&self) -> io::Result<()> {
+        const DOT: &[u8] = b".\0";
+        const TMPFS: &[u8] = b"tmpfs\0";
+        const ROOT_OPTIONS: &[u8] = b"mode=755,size=1m\0";
+        let dot = DOT.as_ptr() as *const libc::c_char;
+        let tmpfs = TMPFS.as_ptr() as *const libc::c_char;
+        let null = std::ptr::null();
+        let no_data = std::ptr::null();
+        unsafe {
+            // nothing done here may leak back into the host's namespace
+            check(libc::mount(
+                null,
+                b"/\0".as_ptr() as *const libc::c_char,
+                null,
+                libc::MS_REC | libc::MS_PRIVATE,
+                no_data,
+            ))?;
+            // mounts from the host's namespace can't be bind mounted
+            let fd = libc::open(
+                self.root.as_ptr(),
+                libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let duplicated = libc::dup3(fd, self.game_dir.as_raw_fd(), libc::O_CLOEXEC);
+            libc::close(fd);
+            if duplicated < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            check(libc::mount(
+                tmpfs,
+                self.root.as_ptr(),
+                tmpfs,
+                libc::MS_NOSUID | libc::MS_NODEV,
+                ROOT_OPTIONS.as_ptr() as *const libc::c_void,
+            ))?;
+            for dir in &self.dirs {
+                check(libc::mkdir(dir.as_ptr(), 0o755))?;
+            }
+            for file in &self.files {
+                let fd = libc::open(
+                    file.as_ptr(),
+                    libc::O_CREAT | libc::O_EXCL | libc::O_WRONLY | libc::O_CLOEXEC,
+                    0o644,
+                );
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                libc::close(fd);
+            }
+
+            check(libc::mount(
+                self.game_dir_fd.as_ptr(),
+                self.game_mount.as_ptr(),
+                null,
+                libc::MS_BIND,
+                no_data,
+            ))?;
+            check(libc::mount(
+                null,
+                self.game_mount.as_ptr(),
+                null,
+                libc::MS_BIND | libc::MS_REMOUNT | libc::MS_NOSUID | libc::MS_NODEV,
+                no_data,
+            ))?;
+            for (source, target) in &self.mounts {
+                check(libc::mount(
+                    source.as_ptr(),
+                    target.as_ptr(),
+                    null,
+                    libc::MS_BIND,
+                    no_data,
+                ))?;
+                // bind mounts only become read-only when remounted
+                check(libc::mount(
+                    null,
+                    target.as_ptr(),
+                    null,
+                    libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | libc::MS_NOSUID,
+                    no_data,
+                ))?;
+            }
+            check(libc::mount(
+                tmpfs,
+                self.tmp.as_ptr(),
+                tmpfs,
+                libc::MS_NOSUID | libc::MS_NODEV,
+                self.tmp_options.as_ptr() as *const libc::c_void,
+            ))?;
+            check(libc::mount(
+                null,
+                self.root.as_ptr(),
+                null,
+                libc::MS_REMOUNT | libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV,
+                no_data,
+            ))?;
+
+            // the old root is stacked under the new one and detached right away
+            check(libc::chdir(self.root.as_ptr()))?;
+            check(libc::syscall(libc::SYS_pivot_root, dot, dot) as libc::c_int)?;
+            check(libc::umount2(dot, libc::MNT_DETACH))?;
+            check(libc::chdir(self.workdir.as_ptr()))?;
+        }
+        Ok(())
+    }
+}
+
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().to_owned().into_vec()).map_err(io::Error::other)
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Hands the game directory to the sandbox's user. The walk goes through directory file
+/// descriptors and never follows symlinks, a process still running in the directory can't
+/// redirect it. Hard links are left alone, they could point outside of the directory.
+fn chown_all(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    let dir = Dir::open(
+        path,
+        OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_NOFOLLOW,
+        Mode::empty(),
+    )
+    .map_err(errno_to_io)?;
+    chown_dir(dir, Uid::from_raw(uid), Gid::from_raw(gid))
+}
+
+fn chown_dir(mut dir: Dir, uid: Uid, gid: Gid) -> io::Result<()> {
+    let fd = dir.as_raw_fd();
+    fchown(fd, Some(uid), Some(gid)).map_err(errno_to_io)?;
+    let names = dir
+        .iter()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_owned())
+        .filter(|name| name.as_bytes() != b"." && name.as_bytes() != b"..")
+        .collect::<Vec<_>>();
+    for name in names {
+        let stat =
+            fstatat(fd, name.as_c_str(), AtFlags::AT_SYMLINK_NOFOLLOW).map_err(errno_to_io)?;
+        match stat.st_mode & libc::S_IFMT {
+            libc::S_IFDIR => chown_dir(
+                Dir::openat(
+                    fd,
+                    name.as_c_str(),
+                    OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_NOFOLLOW,
+                    Mode::empty(),
+                )
+                .map_err(errno_to_io)?,
+                uid,
+                gid,
+            )?,
+            libc::S_IFLNK => {}
+            _ if stat.st_nlink == 1 => fchownat(
+                Some(fd),
+                name.as_c_str(),
+                Some(uid),
+                Some(gid),
+                FchownatFlags::NoFollowSymlink,
+            )
+            .map_err(errno_to_io)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn join_cgroup(procs: &CString) -> io::Result<()> {
+    unsafe {
+        let fd = libc::open(procs.as_ptr(), libc::O_WRONLY);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // "0" moves the writing process
+        let written = libc::write(fd, b"0".as_ptr() as *const libc::c_void, 1);
+        libc::close(fd);
+        if written != 1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn limit(resource: Resource, value: u64) -> io::Result<()> {
+    setrlimit(resource, Some(value), Some(value)).map_err(errno_to_io)
+}
+
+fn errno_to_io(errno: nix::errno::Errno) -> io::Error {
+    io::Error::from_raw_os_error(errno as i32)
+}
+
+fn install_seccomp(filter: &[sock_filter]) -> io::Result<()> {
+    let prog = sock_fprog {
+        len: filter.len() as libc::c_ushort,
+        filter: filter.as_ptr() as *mut sock_filter,
+    };
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &prog as *const sock_fprog,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn bpf_stmt(code: u32, k: u32) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Deny list filter, anything not in [`DENIED_SYSCALLS`] is allowed and so is `clone`
+/// unless it asks for new namespaces. Syscalls made with a foreign calling convention
+/// kill the process, so that the list can't be sidestepped.
+fn seccomp_filter() -> Vec<sock_filter> {
+    // offsets into struct seccomp_data
+    const NR: u32 = 0;
+    const ARCH: u32 = 4;
+    // low half of the first argument, the flags of clone
+    #[cfg(target_endian = "little")]
+    const FLAGS: u32 = 16;
+    let deny = libc::SECCOMP_RET_ERRNO | (libc::EPERM as u32 & libc::SECCOMP_RET_DATA);
+
+    let mut filter = vec![
+        bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARCH),
+        bpf_jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            AUDIT_ARCH,
+            1,
+            0,
+        ),
+        bpf_stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+        bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, NR),
+    ];
+    #[cfg(target_arch = "x86_64")]
+    {
+        // x32 syscalls share the architecture but have this bit set
+        const X32_SYSCALL_BIT: u32 = 0x40000000;
+        filter.push(bpf_jump(
+            libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K,
+            X32_SYSCALL_BIT,
+            0,
+            1,
+        ));
+        filter.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, deny));
+    }
+    // clone3 passes its flags in memory the filter can't read, glibc falls back to clone
+    filter.push(bpf_jump(
+        libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+        libc::SYS_clone3 as u32,
+        0,
+        1,
+    ));
+    filter.push(bpf_stmt(
+        libc::BPF_RET | libc::BPF_K,
+        libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32,
+    ));
+    // threads and processes are fine, new namespaces aren't
+    filter.push(bpf_jump(
+        libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+        libc::SYS_clone as u32,
+        0,
+        4,
+    ));
+    filter.push(bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, FLAGS));
+    filter.push(bpf_jump(
+        libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K,
+        CLONE_NAMESPACE_FLAGS,
+        0,
+        1,
+    ));
+    filter.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, deny));
+    filter.push(bpf_stmt(
+        libc::BPF_RET | libc::BPF_K,
+        libc::SECCOMP_RET_ALLOW,
+    ));
+    for syscall in DENIED_SYSCALLS {
+        filter.push(bpf_jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            *syscall as u32,
+            0,
+            1,
+        ));
+        filter.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, deny));
+    }
+    filter.push(bpf_stmt(
+        libc::BPF_RET | libc::BPF_K,
+        libc::SECCOMP_RET_ALLOW,
+    ));
+    filter
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process::Stdio, time::Duration};
+
+    use nix::unistd::Uid;
+
+    use super::{parse_oom_kills, NativeBackend};
+    use crate::{
+        backend::{ExecutionBackend, ProcessSpec},
+        config::NativeConfig,
+        game_dir::GameDir,
+    };
+
+    fn game_dir() -> GameDir {
+        GameDir::new(
+            std::env::temp_dir().to_str().unwrap(),
+            "native-test",
+            Duration::ZERO,
+        )
+        .unwrap()
+    }
+
+    fn unprivileged_backend() -> NativeBackend {
+        NativeBackend::new(NativeConfig {
+            use_cgroups: false,
+            unshare_namespaces: false,
+            max_open_files: 32,
+            ..Default::default()
+        })
+    }
+
+    fn run(backend: &NativeBackend, script: &str) -> std::process::Output {
+        run_in(backend, &game_dir(), script)
+    }
+
+    fn run_in(backend: &NativeBackend, dir: &GameDir, script: &str) -> std::process::Output {
+        backend
+            .spawn(
+                &ProcessSpec {
+                    game_dir: dir.get_path(),
+                    image: "",
+                    mounts: &[],
+                    command: vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()],
+                    time_limit: 5,
+                    memory_limit: 100,
+                    cpus: "1",
+                    interactive: false,
+                },
+                Stdio::null(),
+                Stdio::piped(),
+            )
+            .unwrap()
+            .wait_with_output()
+            .unwrap()
+            .output
+    }
+
+    #[test]
+    fn oom_kills_are_read_from_memory_events() {
+        let events = "low 0\nhigh 0\nmax 12\noom 1\noom_kill 1\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kills(events), 1);
+        assert_eq!(
+            parse_oom_kills("low 0\nhigh 0\nmax 0\noom 0\noom_kill 0\n"),
+            0
+        );
+    }
+
+    #[test]
+    fn resource_usage_is_reported() {
+        let dir = game_dir();
+        let out = unprivileged_backend()
+            .spawn(
+                &ProcessSpec {
+                    game_dir: dir.get_path(),
+                    image: "",
+                    mounts: &[],
+                    command: vec!["sleep".to_owned(), "0.2".to_owned()],
+                    time_limit: 5,
+                    memory_limit: 100,
+                    cpus: "1",
+                    interactive: false,
+                },
+                Stdio::null(),
+                Stdio::null(),
+            )
+            .unwrap()
+            .wait_with_output()
+            .unwrap();
+        assert!(out.output.status.success());
+        assert!(out.usage.wall_time_ms >= 200);
+        assert!(out.usage.cpu_time_ms.is_some());
+        assert!(out.usage.peak_memory_kb.unwrap() > 0);
+    }
+
+    #[test]
+    fn rlimits_are_applied() {
+        let out = run(&unprivileged_backend(), "ulimit -n");
+        assert!(out.status.success());
+        assert_eq!(String::from_utf8(out.stdout).unwrap().trim(), "32");
+    }
+
+    #[test]
+    fn denied_syscalls_fail() {
+        let out = run(
+            &unprivileged_backend(),
+            "python3 -c 'import socket; socket.socket()'",
+        );
+        assert!(!out.status.success());
+        assert!(String::from_utf8(out.stderr)
+            .unwrap()
+            .contains("PermissionError"));
+    }
+
+    #[test]
+    fn namespaces_cant_be_created() {
+        let out = run(
+            &unprivileged_backend(),
+            "python3 -c '
+import ctypes, threading
+libc = ctypes.CDLL(None, use_errno=True)
+thread = threading.Thread(target=print)
+thread.start()
+thread.join()
+print(libc.syscall(435, 0, 0), ctypes.get_errno())
+print(libc.syscall(56, 0x10000000, 0, 0, 0, 0), ctypes.get_errno())
+'",
+        );
+        assert!(out.status.success());
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        let lines = stdout.lines().collect::<Vec<_>>();
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(lines, ["", "-1 38", "-1 1"]);
+    }
+
+    #[test]
+    fn sandbox_root_is_the_game_dir() {
+        if !Uid::effective().is_root() {
+            return;
+        }
+        let dir = game_dir();
+        std::fs::write(format!("{}/run.py", dir.get_path()), "").unwrap();
+        let backend = NativeBackend::new(NativeConfig {
+            use_cgroups: false,
+            ..Default::default()
+        });
+        let out = run_in(
+            &backend,
+            &dir,
+            "id -u; pwd; ls /; ls; touch /usr/x || echo ro; touch /tmp/x out && echo rw",
+        );
+        assert!(out.status.success(), "{:?}", out);
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        let lines = stdout.lines().collect::<Vec<_>>();
+        assert_eq!(lines[..2], ["65534", "/game"]);
+        assert!(lines.contains(&"usr") && lines.contains(&"run.py"));
+        assert!(!lines.contains(&"root") && !lines.contains(&"home"));
+        assert_eq!(lines[lines.len() - 2..], ["ro", "rw"]);
+        assert!(std::path::Path::new(&format!("{}/out", dir.get_path())).exists());
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    convert::TryFrom,
+    fmt::Display,
+    fs::File,
+    io::{self, Write},
+    thread::JoinHandle,
+};
+
+use serde::Deserialize;
+
+use crate::{error::SimulatorError, request::GameParameters};
+
+/// Version of the initial input the simulator and the players read, it has to match the
+/// one the simulator image and the boilerplates were built for
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(try_from = "u32")]
+pub enum ProtocolVersion {
+    /// The original format. Defenders carry a speed column that is always 0, since they
+    /// can't move, and every map cell is followed by a space.
+    #[default]
+    V1,
+    /// Starts with a `PROTOCOL 2` line, troops are prefixed with their id so that the
+    /// defenders on the map can be looked up, defenders have no speed column and values
+    /// are only separated by spaces.
+    V2,
+}
+
+impl TryFrom<u32> for ProtocolVersion {
+    type Error = String;
+    fn try_from(version: u32) -> Result<Self, Self::Error> {
+        match version {
+            1 => Ok(ProtocolVersion::V1),
+            2 => Ok(ProtocolVersion::V2),
+            _ => Err(format!(
+                "unknown protocol version {}, expected 1 or 2",
+                version
+            )),
+        }
+    }
+}
+
+impl std::str::FromStr for ProtocolVersion {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = s
+            .parse::<u32>()
+            .map_err(|_| format!("unknown protocol version {}, expected 1 or 2", s))?;
+        ProtocolVersion::try_from(version)
+    }
+}
+
+/// Writes whitespace separated values, one record per line
+struct InputWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> InputWriter<W> {
+    fn line<T: Display>(&mut self, values: &[T]) -> io::Result<()> {
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b" ")?;
+            }
+            write!(self.writer, "{}", value)?;
+        }
+        self.writer.write_all(b"\n")
+    }
+
+    /// Each value is followed by a space, like the V1 map rows
+    fn padded_line<T: Display>(&mut self, values: &[T]) -> io::Result<()> {
+        for value in values {
+            write!(self.writer, "{} ", value)?;
+        }
+        self.writer.write_all(b"\n")
+    }
+}
+
+/// Serializes the game's parameters and the map the player attacks
+pub fn write_initial_input<W: Write>(
+    writer: W,
+    version: ProtocolVersion,
+    parameters: &GameParameters,
+    map: &[Vec<u8>],
+) -> io::Result<()> {
+    let mut input = InputWriter { writer };
+    if version == ProtocolVersion::V2 {
+        input.line(&["PROTOCOL", "2"])?;
+    }
+    input.line(&[parameters.no_of_turns, parameters.no_of_coins])?;
+
+    input.line(&[parameters.attackers.len()])?;
+    for attacker in &parameters.attackers {
+        let stats = [
+            attacker.hp,
+            attacker.range,
+            attacker.attack_power,
+            attacker.speed,
+            attacker.price,
+        ];
+        match version {
+            ProtocolVersion::V1 => input.line(&stats)?,
+            ProtocolVersion::V2 => input.line(&[&[attacker.id][..], &stats].concat())?,
+        }
+    }
+
+    input.line(&[parameters.defenders.len()])?;
+    for defender in &parameters.defenders {
+        match version {
+            ProtocolVersion::V1 => input.line(&[
+                defender.hp,
+                defender.range,
+                defender.attack_power,
+                0,
+                defender.price,
+            ])?,
+            ProtocolVersion::V2 => input.line(&[
+                defender.id,
+                defender.hp,
+                defender.range,
+                defender.attack_power,
+                defender.price,
+            ])?,
+        }
+    }
+
+    let columns = map.first().map(|row| row.len()).unwrap_or(0);
+    input.line(&[map.len(), columns])?;
+    for row in map {
+        match version {
+            ProtocolVersion::V1 => input.padded_line(row)?,
+            ProtocolVersion::V2 => input.line(row)?,
+        }
+    }
+    input.writer.flush()
+}
+
+/// Writes the initial input to every fifo, it's serialized once for all of them. Large
+/// maps don't fit in a pipe's buffer, so it's written on another thread while the
+/// processes reading it are started.
+pub fn send_initial_input(
+    fifos: &[&File],
+    version: ProtocolVersion,
+    parameters: &GameParameters,
+    map: &[Vec<u8>],
+) -> Result<InitialInput, SimulatorError> {
+    let mut input = vec![];
+    let fifos: Vec<File> = write_initial_input(&mut input, version, parameters, map)
+        .and_then(|_| fifos.iter().map(|fifo| fifo.try_clone()).collect())
+        .map_err(initial_input_error)?;
+    Ok(InitialInput(std::thread::spawn(move || {
+        fifos.iter().try_for_each(|mut fifo| fifo.write_all(&input))
+    })))
+}
+
+/// Initial input being written, see [`send_initial_input`]
+pub struct InitialInput(JoinHandle<io::Result<()>>);
+
+impl InitialInput {
+    /// Waits until every fifo got the whole input, or one of them was closed
+    pub fn finish(self) -> Result<(), SimulatorError> {
+        self.0
+            .join()
+            .map_err(|_| io::Error::other("writer panicked"))
+            .and_then(|result| result)
+            .map_err(initial_input_error)
+    }
+}
+
+fn initial_input_error(e: io::Error) -> SimulatorError {
+    SimulatorError::FifoCreationError(format!("Failed to send the initial input: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read, os::unix::io::FromRawFd, path::PathBuf};
+
+    use super::{send_initial_input, write_initial_input, ProtocolVersion};
+    use crate::{
+        error::SimulatorError,
+        request::{Attacker, Defender, GameParameters},
+    };
+
+    fn parameters() -> GameParameters {
+        GameParameters {
+            attackers: vec![
+                Attacker {
+                    id: 1,
+                    hp: 10,
+                    range: 3,
+                    attack_power: 3,
+                    speed: 3,
+                    price: 1,
+                },
+                Attacker {
+                    id: 2,
+                    hp: 20,
+                    range: 1,
+                    attack_power: 8,
+                    speed: 1,
+                    price: 4,
+                },
+            ],
+            defenders: vec![
+                Defender {
+                    id: 1,
+                    hp: 30,
+                    range: 4,
+                    attack_power: 5,
+                    price: 2,
+                },
+                Defender {
+                    id: 2,
+                    hp: 50,
+                    range: 2,
+                    attack_power: 10,
+                    price: 6,
+                },
+            ],
+            no_of_turns: 500,
+            no_of_coins: 1000,
+        }
+    }
+
+    fn map() -> Vec<Vec<u8>> {
+        let mut map = vec![vec![0; 5]; 3];
+        map[0][4] = 1;
+        map[2][1] = 2;
+        map
+    }
+
+    /// Compares the output with `testdata/<name>`, which is rewritten instead when
+    /// `UPDATE_GOLDEN` is set
+    fn assert_golden(name: &str, version: ProtocolVersion) {
+        let mut output = vec![];
+        write_initial_input(&mut output, version, &parameters(), &map()).unwrap();
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, &output).unwrap();
+        }
+        let golden = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), golden);
+    }
+
+    #[test]
+    fn initial_input_v1_matches_golden() {
+        assert_golden("initial_input_v1.txt", ProtocolVersion::V1);
+    }
+
+    #[test]
+    fn initial_input_v2_matches_golden() {
+        assert_golden("initial_input_v2.txt", ProtocolVersion::V2);
+    }
+
+    #[test]
+    fn broken_pipe_is_an_error() {
+        let (read, write) = nix::unistd::pipe().unwrap();
+        nix::unistd::close(read).unwrap();
+        let fifo = unsafe { File::from_raw_fd(write) };
+        assert!(matches!(
+            send_initial_input(&[&fifo], ProtocolVersion::V1, &parameters(), &map())
+                .and_then(|input| input.finish()),
+            Err(SimulatorError::FifoCreationError(_))
+        ));
+    }
+
+    #[test]
+    fn large_maps_dont_block() {
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let (mut read, write) = unsafe { (File::from_raw_fd(read), File::from_raw_fd(write)) };
+        // far more than a pipe's buffer, nothing reads until the input was sent
+        let map = vec![vec![1; 300]; 300];
+        let input =
+            send_initial_input(&[&write], ProtocolVersion::V1, &parameters(), &map).unwrap();
+        drop(write);
+        let mut received = vec![];
+        read.read_to_end(&mut received).unwrap();
+        input.finish().unwrap();
+        assert!(received.len() > 2 * 300 * 300);
+    }
+}
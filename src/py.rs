@@ -1,44 +1,32 @@
-use std::{
-    fs::File,
-    process::{Command, Stdio},
-};
-
-use crate::{error::SimulatorError, RUNTIME_MEMORY_LIMIT, RUNTIME_TIME_LIMIT};
+use crate::{backend::Mount, runner::LanguageRunner};
 
 pub struct Runner {
-    current_dir: String,
+    runner_image: String,
 }
 
 impl Runner {
-    pub fn new(current_dir: String) -> Self {
-        Runner { current_dir }
+    pub fn new(runner_image: String) -> Self {
+        Runner { runner_image }
+    }
+}
+
+impl LanguageRunner for Runner {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+    fn boilerplate_dir(&self) -> &'static str {
+        "player_code/python"
+    }
+    fn source_file(&self) -> &'static str {
+        "run.py"
+    }
+    fn runner_image(&self) -> &str {
+        &self.runner_image
+    }
+    fn run_mounts(&self) -> Vec<Mount> {
+        vec![Mount::new("run.py", "/player_code/run.py")]
     }
-    pub fn run(&self, stdin: File, stdout: File) -> Result<std::process::Child, SimulatorError> {
-        Command::new("timeout".to_owned())
-            .args([
-                "--signal=KILL",
-                RUNTIME_TIME_LIMIT,
-                "docker",
-                "run",
-                &format!("--memory={}", RUNTIME_MEMORY_LIMIT),
-                &format!("--memory-swap={}", RUNTIME_MEMORY_LIMIT),
-                "--cpus=1",
-                "--rm",
-                "-i",
-                "-v",
-                format!("{}/run.py:/player_code/run.py", self.current_dir.as_str()).as_str(),
-                "ghcr.io/delta/codecharacter-python-runner:latest",
-            ])
-            .current_dir(&self.current_dir)
-            .stdin(stdin)
-            .stdout(stdout)
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| {
-                SimulatorError::UnidentifiedError(format!(
-                    "Couldnt spawn the python runner process: {}",
-                    err
-                ))
-            })
+    fn native_run_command(&self) -> Vec<String> {
+        vec!["python3".to_owned(), "run.py".to_owned()]
     }
 }
@@ -1,7 +1,11 @@
+use std::collections::BTreeMap;
+
 use serde::de;
 use serde::Deserialize;
 use serde::Deserializer;
 
+use crate::{config::LimitOverrides, game_log::LogFormat};
+
 #[derive(Deserialize, Debug, PartialEq)]
 pub struct Attacker {
     pub id: u32,
@@ -29,23 +33,120 @@ pub struct GameParameters {
     pub no_of_coins: u32,
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
+#[derive(Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Language {
     CPP,
     JAVA,
     PYTHON,
+    RUST,
+    GO,
+    JAVASCRIPT,
+    TYPESCRIPT,
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
 pub struct GameRequest {
     pub game_id: String,
     pub parameters: GameParameters,
+    #[serde(default)]
+    pub source_code: String,
+    /// Source files by path, relative to the player's directory, given instead of
+    /// `source_code` to split the code into modules
+    #[serde(default)]
+    pub files: Option<BTreeMap<String, String>>,
+    pub language: Language,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub map: Vec<Vec<u8>>,
+    /// Overrides the configured `log_format` for this game
+    #[serde(default)]
+    pub log_format: Option<LogFormat>,
+    /// Overrides the configured limits, up to `max_limits`
+    #[serde(default)]
+    pub limits: LimitOverrides,
+}
+
+/// Discriminates the request types, requests without it are single player games
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub enum GameType {
+    #[default]
+    NORMAL,
+    PVP,
+}
+
+/// One side of a PvP game
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct PlayerCode {
+    #[serde(default)]
     pub source_code: String,
+    #[serde(default)]
+    pub files: Option<BTreeMap<String, String>>,
     pub language: Language,
+    /// The map this player defends, it is attacked by the other player
     #[serde(deserialize_with = "deserialize_from_str")]
     pub map: Vec<Vec<u8>>,
 }
 
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct PvPGameRequest {
+    pub game_id: String,
+    pub parameters: GameParameters,
+    pub player1: PlayerCode,
+    pub player2: PlayerCode,
+    #[serde(default)]
+    pub log_format: Option<LogFormat>,
+    /// Applies to both players and the simulator
+    #[serde(default)]
+    pub limits: LimitOverrides,
+}
+
+#[derive(Deserialize)]
+struct RequestHeader {
+    #[serde(default)]
+    game_type: GameType,
+}
+
+/// Any request the driver can execute
+#[derive(Debug, PartialEq)]
+pub enum DriverRequest {
+    Normal(GameRequest),
+    PvP(PvPGameRequest),
+}
+
+impl DriverRequest {
+    /// Picks the request type from the `game_type` field before deserializing the rest,
+    /// so that errors point at the actual problem
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        let header: RequestHeader = serde_json::from_str(json)?;
+        match header.game_type {
+            GameType::NORMAL => serde_json::from_str(json).map(DriverRequest::Normal),
+            GameType::PVP => serde_json::from_str(json).map(DriverRequest::PvP),
+        }
+    }
+
+    /// Languages of the submissions, one per player
+    pub fn languages(&self) -> Vec<Language> {
+        match self {
+            DriverRequest::Normal(request) => vec![request.language],
+            DriverRequest::PvP(request) => vec![request.player1.language, request.player2.language],
+        }
+    }
+
+    pub fn game_id(&self) -> &str {
+        match self {
+            DriverRequest::Normal(request) => &request.game_id,
+            DriverRequest::PvP(request) => &request.game_id,
+        }
+    }
+}
+
+/// Message on the control exchange, acted on by every driver
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum ControlRequest {
+    /// Stops the game if it is queued or running, its status becomes `CANCELLED`
+    Cancel { game_id: String },
+}
+
 // Reference: https://serde.rs/attr-bound.html
 fn deserialize_from_str<'de, D>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error>
 where
@@ -58,7 +159,10 @@ where
 #[cfg(test)]
 mod tests {
 
-    use super::{Attacker, Defender, GameParameters, GameRequest};
+    use super::{
+        Attacker, ControlRequest, Defender, DriverRequest, GameParameters, GameRequest, Language,
+        LimitOverrides, PlayerCode,
+    };
     #[test]
     pub fn deserealization_test() {
         // An example request that we might get from backend
@@ -106,10 +210,93 @@ mod tests {
             },
             language: super::Language::PYTHON,
             source_code: r#"print(x)"#.to_owned(),
+            files: None,
             map: vec![vec![1, 0], vec![0, 2]],
+            log_format: None,
+            limits: LimitOverrides::default(),
         };
         let deserealized_example_request: GameRequest =
             serde_json::from_str(example_request).unwrap();
         assert_eq!(deserealized_example_request, expected_deserealized_struct);
     }
+
+    #[test]
+    pub fn pvp_deserialization_test() {
+        let example_request = r#"{"game_type":"PVP","game_id":"1","parameters":{"attackers":[],"defenders":[],"no_of_turns":500,"no_of_coins":1000},"player1":{"source_code":"print(x)","language":"PYTHON","map":"[[1,0]]"},"player2":{"source_code":"int main() {}","language":"CPP","map":"[[0,2]]"},"limits":{"runtime_time":20}}"#;
+
+        let request = match DriverRequest::from_json(example_request).unwrap() {
+            DriverRequest::PvP(request) => request,
+            other => panic!("Expected a PvP request, got {:?}", other),
+        };
+        assert_eq!(
+            request.player1,
+            PlayerCode {
+                source_code: "print(x)".to_owned(),
+                files: None,
+                language: Language::PYTHON,
+                map: vec![vec![1, 0]],
+            }
+        );
+        assert_eq!(request.player2.language, Language::CPP);
+        assert_eq!(request.player2.map, vec![vec![0, 2]]);
+        assert_eq!(request.limits.runtime_time, Some(20));
+        assert_eq!(request.limits.runtime_memory, None);
+
+        let normal_request = r#"{"game_id":"2","parameters":{"attackers":[],"defenders":[],"no_of_turns":500,"no_of_coins":1000},"source_code":"","language":"JAVA","map":"[]"}"#;
+        assert!(matches!(
+            DriverRequest::from_json(normal_request),
+            Ok(DriverRequest::Normal(GameRequest {
+                language: Language::JAVA,
+                ..
+            }))
+        ));
+
+        let files_request = r#"{"game_id":"5","parameters":{"attackers":[],"defenders":[],"no_of_turns":500,"no_of_coins":1000},"files":{"run.py":"import bot","bot.py":"x = 1"},"language":"PYTHON","map":"[]"}"#;
+        match DriverRequest::from_json(files_request).unwrap() {
+            DriverRequest::Normal(request) => {
+                assert_eq!(request.source_code, "");
+                assert_eq!(request.files.unwrap()["bot.py"], "x = 1");
+            }
+            other => panic!("Expected a normal request, got {:?}", other),
+        }
+
+        let err = DriverRequest::from_json(r#"{"game_type":"PVP","game_id":"3"}"#).unwrap_err();
+        assert!(err.to_string().contains("parameters"));
+    }
+
+    #[test]
+    pub fn language_deserialization_test() {
+        let example_request = r#"{"game_id":"4","parameters":{"attackers":[],"defenders":[],"no_of_turns":500,"no_of_coins":1000},"source_code":"pub fn run() {}","language":"RUST","map":"[[0]]"}"#;
+        let request = match DriverRequest::from_json(example_request).unwrap() {
+            DriverRequest::Normal(request) => request,
+            other => panic!("Expected a normal request, got {:?}", other),
+        };
+        assert_eq!(request.language, Language::RUST);
+        assert_eq!(request.source_code, "pub fn run() {}");
+
+        assert!(serde_json::from_str::<Language>(r#""rust""#).is_err());
+
+        for (json, language) in [
+            (r#""GO""#, Language::GO),
+            (r#""JAVASCRIPT""#, Language::JAVASCRIPT),
+            (r#""TYPESCRIPT""#, Language::TYPESCRIPT),
+        ] {
+            assert_eq!(serde_json::from_str::<Language>(json).unwrap(), language);
+        }
+    }
+
+    #[test]
+    pub fn control_deserialization_test() {
+        let request: ControlRequest =
+            serde_json::from_str(r#"{"action":"cancel","game_id":"0fa0f12d"}"#).unwrap();
+        assert_eq!(
+            request,
+            ControlRequest::Cancel {
+                game_id: "0fa0f12d".to_owned()
+            }
+        );
+        assert!(
+            serde_json::from_str::<ControlRequest>(r#"{"action":"pause","game_id":"1"}"#).is_err()
+        );
+    }
 }
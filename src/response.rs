@@ -7,6 +7,24 @@ pub enum GameStatusEnum {
     EXECUTING,
     EXECUTED,
     EXECUTE_ERROR,
+    /// Cancelled before it was executed
+    CANCELLED,
+}
+
+/// Resources used by a single process. Containers are sampled while they run, so their cpu
+/// time misses up to the last 100ms, and neither is known without cgroup v2.
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct ResourceUsage {
+    pub wall_time_ms: u64,
+    pub cpu_time_ms: Option<u64>,
+    pub peak_memory_kb: Option<u64>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct GameResourceUsage {
+    /// Not set for interpreted languages
+    pub compilation: Option<ResourceUsage>,
+    pub run: Option<ResourceUsage>,
 }
 
 #[derive(Serialize, Debug, PartialEq)]
@@ -15,6 +33,15 @@ pub struct GameResult {
     pub coins_used: u64,
     pub has_errors: bool,
     pub log: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_usage: Option<GameResourceUsage>,
+}
+
+/// Result of each player in a PvP game, from the point of view of that player as the attacker
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PvPGameResult {
+    pub player1: GameResult,
+    pub player2: GameResult,
 }
 
 #[derive(Serialize, Debug, PartialEq)]
@@ -22,12 +49,18 @@ pub struct GameStatus {
     pub game_id: String,
     pub game_status: GameStatusEnum,
     pub game_result: Option<GameResult>,
+    /// Only set for successfully executed PvP games, errors are reported in `game_result`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pvp_game_result: Option<PvPGameResult>,
+    /// Kind of error an `EXECUTE_ERROR` status was created for, only used for the metrics
+    #[serde(skip)]
+    pub error: Option<&'static str>,
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::{GameStatus, GameStatusEnum};
+    use super::{GameResourceUsage, GameResult, GameStatus, GameStatusEnum, ResourceUsage};
     #[test]
     pub fn serialization_test() {
         // An example respone
@@ -37,10 +70,43 @@ mod tests {
             game_id: "030af985-f4b5-4914-94d8-e559576449e3".to_string(),
             game_status: GameStatusEnum::EXECUTING,
             game_result: None,
+            pvp_game_result: None,
+            error: None,
         };
 
         let serialized_game_status = serde_json::to_string(&game_status).unwrap();
 
         assert_eq!(serialized_game_status, expected_response);
     }
+
+    #[test]
+    pub fn resource_usage_serialization_test() {
+        let expected_response = r#"{"game_id":"1","game_status":"EXECUTED","game_result":{"destruction_percentage":75.0,"coins_used":10,"has_errors":false,"log":"","resource_usage":{"compilation":null,"run":{"wall_time_ms":1200,"cpu_time_ms":null,"peak_memory_kb":null}}}}"#;
+
+        let game_status = GameStatus {
+            game_id: "1".to_string(),
+            game_status: GameStatusEnum::EXECUTED,
+            game_result: Some(GameResult {
+                destruction_percentage: 75.0,
+                coins_used: 10,
+                has_errors: false,
+                log: "".to_owned(),
+                resource_usage: Some(GameResourceUsage {
+                    compilation: None,
+                    run: Some(ResourceUsage {
+                        wall_time_ms: 1200,
+                        cpu_time_ms: None,
+                        peak_memory_kb: None,
+                    }),
+                }),
+            }),
+            pvp_game_result: None,
+            error: None,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&game_status).unwrap(),
+            expected_response
+        );
+    }
 }
@@ -0,0 +1,249 @@
+use std::{collections::HashMap, fs::File, process::Stdio};
+
+use crate::{
+    backend::{ExecutionBackend, Mount, Process, ProcessSpec},
+    config::{Images, Limits},
+    cpp,
+    error::SimulatorError,
+    go, handle_process, java, js, py,
+    request::Language,
+    response::ResourceUsage,
+    rust,
+};
+
+/// Everything the driver needs to know to compile and run player code in a given language.
+///
+/// Implementations only describe the language, starting the processes is left to the
+/// [`ExecutionBackend`].
+pub trait LanguageRunner: Send + Sync {
+    /// Human readable name used in error messages
+    fn name(&self) -> &'static str;
+
+    /// Directory containing the boilerplate which is copied into the game directory
+    fn boilerplate_dir(&self) -> &'static str;
+
+    /// File (relative to the game directory) the player's source code is written to
+    fn source_file(&self) -> &'static str;
+
+    /// Image used for the compilation step, `None` if the language is interpreted
+    fn compiler_image(&self) -> Option<&str> {
+        None
+    }
+
+    fn compile_mounts(&self) -> Vec<Mount> {
+        vec![]
+    }
+
+    /// Compilation command for the native backend, run in the game directory
+    fn native_compile_command(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Extension of the sources that are only compiled next to the source file, submitted
+    /// ones in subdirectories are refused instead of being silently left out
+    fn flat_source_extension(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Files and directories (relative to the game directory) produced by the compilation,
+    /// restored from the cache instead of compiling again
+    fn artifacts(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    /// Same as [`LanguageRunner::artifacts`], for the native backend
+    fn native_artifacts(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn compile_cpus(&self) -> &'static str {
+        "2"
+    }
+
+    fn runner_image(&self) -> &str;
+
+    fn run_mounts(&self) -> Vec<Mount>;
+
+    /// Command starting the player for the native backend, run in the game directory
+    fn native_run_command(&self) -> Vec<String>;
+
+    /// Compiles the player's code if the language needs it, returning the resources used.
+    ///
+    /// `files` are the files the player submitted besides the source file.
+    fn compile(
+        &self,
+        backend: &dyn ExecutionBackend,
+        game_dir: &str,
+        limits: &Limits,
+        files: &[String],
+    ) -> Result<Option<ResourceUsage>, SimulatorError> {
+        let image = match self.compiler_image() {
+            Some(image) => image,
+            None => return Ok(None),
+        };
+
+        let mut mounts = self.compile_mounts();
+        mounts.extend(files.iter().map(|file| Mount::player_file(file)));
+        let compile = backend
+            .spawn(
+                &ProcessSpec {
+                    game_dir,
+                    image,
+                    mounts: &mounts,
+                    command: self.native_compile_command(),
+                    time_limit: limits.compilation_time,
+                    memory_limit: limits.compilation_memory,
+                    cpus: self.compile_cpus(),
+                    interactive: false,
+                },
+                Stdio::null(),
+                Stdio::null(),
+            )
+            .map_err(|err| {
+                SimulatorError::UnidentifiedError(format!(
+                    "Couldnt spawn compilation command: {}",
+                    err
+                ))
+            })?;
+
+        handle_process(compile, true, SimulatorError::CompilationError)
+            .map(|(_, usage)| Some(usage))
+    }
+
+    /// Starts the (already compiled) player code, `files` are only needed by interpreted
+    /// languages
+    fn run(
+        &self,
+        backend: &dyn ExecutionBackend,
+        game_dir: &str,
+        limits: &Limits,
+        files: &[String],
+        stdin: File,
+        stdout: File,
+    ) -> Result<Process, SimulatorError> {
+        let mut mounts = self.run_mounts();
+        if self.compiler_image().is_none() {
+            mounts.extend(files.iter().map(|file| Mount::player_file(file)));
+        }
+        backend
+            .spawn(
+                &ProcessSpec {
+                    game_dir,
+                    image: self.runner_image(),
+                    mounts: &mounts,
+                    command: self.native_run_command(),
+                    time_limit: limits.runtime_time,
+                    memory_limit: limits.runtime_memory,
+                    cpus: "1",
+                    interactive: true,
+                },
+                Stdio::from(stdin),
+                Stdio::from(stdout),
+            )
+            .map_err(|err| {
+                SimulatorError::UnidentifiedError(format!(
+                    "Couldnt spawn the {} runner process: {}",
+                    self.name(),
+                    err
+                ))
+            })
+    }
+}
+
+pub struct RunnerRegistry {
+    runners: HashMap<Language, Box<dyn LanguageRunner>>,
+}
+
+impl RunnerRegistry {
+    pub fn new() -> Self {
+        RunnerRegistry {
+            runners: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, language: Language, runner: Box<dyn LanguageRunner>) {
+        self.runners.insert(language, runner);
+    }
+
+    pub fn get(&self, language: &Language) -> Option<&dyn LanguageRunner> {
+        self.runners.get(language).map(|runner| runner.as_ref())
+    }
+
+    /// Registry with every supported language, using the configured images
+    pub fn from_images(images: &Images) -> Self {
+        let mut registry = RunnerRegistry::new();
+        registry.register(
+            Language::CPP,
+            Box::new(cpp::Runner::new(
+                images.cpp_compiler.clone(),
+                images.cpp_runner.clone(),
+            )),
+        );
+        registry.register(
+            Language::JAVA,
+            Box::new(java::Runner::new(
+                images.java_compiler.clone(),
+                images.java_runner.clone(),
+            )),
+        );
+        registry.register(
+            Language::PYTHON,
+            Box::new(py::Runner::new(images.python_runner.clone())),
+        );
+        registry.register(
+            Language::RUST,
+            Box::new(rust::Runner::new(
+                images.rust_compiler.clone(),
+                images.rust_runner.clone(),
+            )),
+        );
+        registry.register(
+            Language::GO,
+            Box::new(go::Runner::new(
+                images.go_compiler.clone(),
+                images.go_runner.clone(),
+            )),
+        );
+        registry.register(
+            Language::JAVASCRIPT,
+            Box::new(js::Runner::javascript(images.javascript_runner.clone())),
+        );
+        registry.register(
+            Language::TYPESCRIPT,
+            Box::new(js::Runner::typescript(
+                images.typescript_compiler.clone(),
+                images.javascript_runner.clone(),
+            )),
+        );
+        registry
+    }
+}
+
+impl Default for RunnerRegistry {
+    fn default() -> Self {
+        RunnerRegistry::from_images(&Images::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RunnerRegistry;
+    use crate::request::Language;
+
+    #[test]
+    fn default_registry_has_all_languages() {
+        let registry = RunnerRegistry::default();
+        for (language, source_file) in [
+            (Language::CPP, "run.cpp"),
+            (Language::JAVA, "Run.java"),
+            (Language::PYTHON, "run.py"),
+            (Language::RUST, "src/run.rs"),
+            (Language::GO, "run.go"),
+            (Language::JAVASCRIPT, "run.js"),
+            (Language::TYPESCRIPT, "run.ts"),
+        ] {
+            let runner = registry.get(&language).unwrap();
+            assert_eq!(runner.source_file(), source_file);
+        }
+    }
+}
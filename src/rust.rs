@@ -0,0 +1,63 @@
+use crate::{backend::Mount, runner::LanguageRunner};
+
+/// The boilerplate is a cargo project whose dependencies are vendored, it is built
+/// offline and the player's code is its `run` module
+pub struct Runner {
+    compiler_image: String,
+    runner_image: String,
+}
+
+impl Runner {
+    pub fn new(compiler_image: String, runner_image: String) -> Self {
+        Runner {
+            compiler_image,
+            runner_image,
+        }
+    }
+}
+
+impl LanguageRunner for Runner {
+    fn name(&self) -> &'static str {
+        "rust"
+    }
+    fn boilerplate_dir(&self) -> &'static str {
+        "player_code/rust"
+    }
+    fn source_file(&self) -> &'static str {
+        "src/run.rs"
+    }
+    fn compiler_image(&self) -> Option<&str> {
+        Some(&self.compiler_image)
+    }
+    fn compile_mounts(&self) -> Vec<Mount> {
+        vec![
+            Mount::new("src/run.rs", "/player_code/src/run.rs"),
+            Mount::new("run", "/player_code/run"),
+        ]
+    }
+    fn native_compile_command(&self) -> Vec<String> {
+        [
+            "sh",
+            "-c",
+            "cargo build --release --offline --quiet && cp target/release/player player",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+    }
+    fn artifacts(&self) -> Vec<&'static str> {
+        vec!["run"]
+    }
+    fn native_artifacts(&self) -> Vec<&'static str> {
+        vec!["player"]
+    }
+    fn runner_image(&self) -> &str {
+        &self.runner_image
+    }
+    fn run_mounts(&self) -> Vec<Mount> {
+        vec![Mount::new("run", "/player_code")]
+    }
+    fn native_run_command(&self) -> Vec<String> {
+        vec!["./player".to_owned()]
+    }
+}
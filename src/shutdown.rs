@@ -0,0 +1,116 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex, OnceLock,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use log::{error, info, warn};
+use nix::sys::signal::{SigSet, Signal};
+
+use crate::backend::processes;
+
+/// Tracks whether the driver is shutting down
+pub struct Shutdown {
+    requested: AtomicBool,
+    killing: AtomicBool,
+    /// Dropped when shutdown is requested, which wakes up every receiver of `stopped`
+    stop: Mutex<Option<Sender<()>>>,
+    stopped: Receiver<()>,
+}
+
+static SHUTDOWN: OnceLock<Shutdown> = OnceLock::new();
+
+/// The driver's shutdown state
+pub fn shutdown() -> &'static Shutdown {
+    SHUTDOWN.get_or_init(Shutdown::new)
+}
+
+/// Blocks SIGTERM and SIGINT and handles them on a dedicated thread instead. Has to be
+/// called before any other thread is started, so that they inherit the signal mask.
+///
+/// The first signal requests a shutdown, the next ones kill the running games.
+pub fn listen() -> nix::Result<()> {
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGTERM);
+    signals.add(Signal::SIGINT);
+    signals.thread_block()?;
+    std::thread::spawn(move || loop {
+        match signals.wait() {
+            Ok(signal) if !shutdown().requested() => {
+                info!("Received {:?}, shutting down", signal);
+                shutdown().request();
+            }
+            Ok(signal) => {
+                warn!("Received {:?} again, killing running games", signal);
+                shutdown().kill_all();
+            }
+            Err(e) => {
+                error!("Failed to wait for signals: {}", e);
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+impl Shutdown {
+    fn new() -> Self {
+        let (stop, stopped) = crossbeam_channel::bounded(0);
+        Shutdown {
+            requested: AtomicBool::new(false),
+            killing: AtomicBool::new(false),
+            stop: Mutex::new(Some(stop)),
+            stopped,
+        }
+    }
+
+    /// Stops taking new games, the ones already running are left alone
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.stop.lock().unwrap().take();
+    }
+
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Disconnected once shutdown is requested, meant to be used in `select!`
+    pub fn stopped(&self) -> Receiver<()> {
+        self.stopped.clone()
+    }
+
+    /// Kills every process started for a game, processes started afterwards are killed
+    /// right away
+    pub fn kill_all(&self) {
+        self.killing.store(true, Ordering::SeqCst);
+        processes().kill_all();
+    }
+
+    /// Whether running games were killed, their errors are caused by the shutdown
+    pub fn killed(&self) -> bool {
+        self.killing.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_channel::TryRecvError;
+
+    use super::Shutdown;
+
+    #[test]
+    fn stopped_is_disconnected_on_request() {
+        let shutdown = Shutdown::new();
+        let stopped = shutdown.stopped();
+        assert_eq!(stopped.try_recv(), Err(TryRecvError::Empty));
+        assert!(!shutdown.requested());
+
+        shutdown.request();
+        assert!(shutdown.requested());
+        assert_eq!(stopped.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(
+            shutdown.stopped().try_recv(),
+            Err(TryRecvError::Disconnected)
+        );
+    }
+}
@@ -1,34 +1,94 @@
 use std::fs::File;
 
-use std::process::{Command, Stdio};
+use std::process::Stdio;
 
-use crate::error::SimulatorError;
-pub struct Simulator {}
+use crate::{
+    backend::{ExecutionBackend, Mount, Process, ProcessSpec},
+    config::Limits,
+    error::SimulatorError,
+};
+
+/// Fifos the PvP simulator talks to the players through, `pN_in` is read by player N
+/// and `pN_out` is written by it
+pub const PVP_FIFOS: [&str; 4] = ["p1_in", "p1_out", "p2_in", "p2_out"];
+
+const PVP_FIFO_MOUNTS: [Mount; 4] = [
+    Mount::new("p1_in", "/game/p1_in"),
+    Mount::new("p1_out", "/game/p1_out"),
+    Mount::new("p2_in", "/game/p2_in"),
+    Mount::new("p2_out", "/game/p2_out"),
+];
+
+pub struct Simulator {
+    image: String,
+    native_command: Vec<String>,
+}
 
 impl Simulator {
-    pub fn run(&self, stdin: File, stdout: File) -> Result<std::process::Child, SimulatorError> {
-        Command::new("timeout".to_owned())
-            .args([
-                "--signal=KILL",
-                "10",
-                "docker",
-                "run",
-                "--memory=100m",
-                "--memory-swap=100m",
-                "--cpus=1",
-                "--rm",
-                "-i",
-                "ghcr.io/delta/codecharacter-simulator:latest",
-            ])
-            .stdin(stdin)
-            .stdout(stdout)
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| {
-                SimulatorError::UnidentifiedError(format!(
-                    "Couldnt spawn the simulator process: {}",
-                    err
-                ))
-            })
+    pub fn new(image: String, native_command: Vec<String>) -> Self {
+        Simulator {
+            image,
+            native_command,
+        }
+    }
+    pub fn run(
+        &self,
+        backend: &dyn ExecutionBackend,
+        game_dir: &str,
+        limits: &Limits,
+        stdin: File,
+        stdout: File,
+    ) -> Result<Process, SimulatorError> {
+        self.spawn(
+            backend,
+            &self.spec(game_dir, limits),
+            Stdio::from(stdin),
+            Stdio::from(stdout),
+        )
+    }
+    /// Starts a PvP simulator, which opens the [`PVP_FIFOS`] in its working directory
+    /// (`/game` in the container) by itself
+    pub fn run_pvp(
+        &self,
+        backend: &dyn ExecutionBackend,
+        game_dir: &str,
+        limits: &Limits,
+    ) -> Result<Process, SimulatorError> {
+        self.spawn(
+            backend,
+            &ProcessSpec {
+                mounts: &PVP_FIFO_MOUNTS,
+                interactive: false,
+                ..self.spec(game_dir, limits)
+            },
+            Stdio::null(),
+            Stdio::null(),
+        )
+    }
+    fn spec<'a>(&'a self, game_dir: &'a str, limits: &Limits) -> ProcessSpec<'a> {
+        ProcessSpec {
+            game_dir,
+            image: &self.image,
+            mounts: &[],
+            command: self.native_command.clone(),
+            time_limit: limits.simulator_time,
+            memory_limit: limits.simulator_memory,
+            cpus: "1",
+            interactive: true,
+        }
+    }
+    fn spawn(
+        &self,
+        backend: &dyn ExecutionBackend,
+        spec: &ProcessSpec,
+        stdin: Stdio,
+        stdout: Stdio,
+    ) -> Result<Process, SimulatorError> {
+        backend.spawn(spec, stdin, stdout).map_err(|err| {
+            SimulatorError::UnidentifiedError(format!(
+                "Couldnt spawn the simulator process: {}",
+                err
+            ))
+        })
     }
 }
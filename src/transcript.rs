@@ -0,0 +1,297 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::io::OwnedFd,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::{error, warn};
+
+/// Records what the simulator and the players send each other, the driver copies the data
+/// between them instead of connecting them directly.
+///
+/// The transcript is a gzipped sequence of records, each one a
+/// `<microseconds since the start> <channel> <length>` line followed by the data and a
+/// newline. It is complete once every copy is done and the last reference is dropped.
+pub struct Transcript {
+    writer: Mutex<Option<GzEncoder<File>>>,
+    started: Instant,
+}
+
+/// Data sent on a channel, like `simulator>player`
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub at: Duration,
+    pub channel: String,
+    pub data: Vec<u8>,
+}
+
+impl Transcript {
+    pub fn create(path: &Path) -> io::Result<Arc<Self>> {
+        let file = File::create(path)?;
+        Ok(Arc::new(Transcript {
+            writer: Mutex::new(Some(GzEncoder::new(file, Compression::default()))),
+            started: Instant::now(),
+        }))
+    }
+
+    fn record(&self, channel: &str, data: &[u8]) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        let writer = match writer.as_mut() {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+        writeln!(
+            writer,
+            "{} {} {}",
+            self.started.elapsed().as_micros(),
+            channel,
+            data.len()
+        )?;
+        writer.write_all(data)?;
+        writer.write_all(b"\n")
+    }
+
+    /// Returns the end to give the process instead of `input`, what is read from `input`
+    /// is recorded and passed on to it
+    pub fn intercept_input(self: &Arc<Self>, channel: &str, input: File) -> io::Result<File> {
+        let (reader, writer) = io::pipe()?;
+        self.copy(channel, input, File::from(OwnedFd::from(writer)));
+        Ok(File::from(OwnedFd::from(reader)))
+    }
+
+    /// Returns the end to give the process instead of `output`, what the process writes
+    /// is recorded and passed on to `output`
+    pub fn intercept_output(self: &Arc<Self>, channel: &str, output: File) -> io::Result<File> {
+        let (reader, writer) = io::pipe()?;
+        self.copy(channel, File::from(OwnedFd::from(reader)), output);
+        Ok(File::from(OwnedFd::from(writer)))
+    }
+
+    /// Copies until `from` is closed or `to` is, like it would be for the process
+    fn copy(self: &Arc<Self>, channel: &str, mut from: File, mut to: File) {
+        let transcript = Arc::clone(self);
+        let channel = channel.to_owned();
+        std::thread::spawn(move || {
+            let mut buffer = [0; 8192];
+            loop {
+                let read = match from.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        warn!("Failed to read {} for the transcript: {}", channel, e);
+                        break;
+                    }
+                };
+                if let Err(e) = transcript.record(&channel, &buffer[..read]) {
+                    error!("Failed to record {}: {}", channel, e);
+                }
+                if to.write_all(&buffer[..read]).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+impl Drop for Transcript {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.lock().unwrap().take() {
+            if let Err(e) = writer.finish() {
+                error!("Failed to write the transcript: {}", e);
+            }
+        }
+    }
+}
+
+/// Where the transcript of the game played in `game_dir` is archived, outside of the game
+/// directory so that it outlives it
+pub fn archive_path(dir: &str, game_dir: &str) -> PathBuf {
+    let name = Path::new(game_dir)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    Path::new(dir).join(format!("{}.gz", name))
+}
+
+/// Removes the transcripts in `dir` that were last written to longer than `retain` ago
+pub fn remove_expired(dir: &Path, retain: Duration) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|extension| extension != "gz") {
+            continue;
+        }
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| modified.elapsed().unwrap_or_default() >= retain)
+            .unwrap_or(false);
+        if expired {
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!("Failed to remove transcript {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Reads back a transcript written by [`Transcript`]
+pub fn read(path: &Path) -> io::Result<Vec<Record>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut reader = BufReader::new(GzDecoder::new(File::open(path)?));
+    let mut records = vec![];
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader.read_line(&mut header)? == 0 {
+            break;
+        }
+        let mut fields = header.trim_end().split(' ');
+        let (at, channel, length) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(at), Some(channel), Some(length)) => (at, channel, length),
+            _ => return Err(invalid("record header is incomplete")),
+        };
+        let at = at.parse().map_err(|_| invalid("invalid record time"))?;
+        let length = length
+            .parse::<usize>()
+            .map_err(|_| invalid("invalid record length"))?;
+        let mut data = vec![0; length + 1];
+        reader.read_exact(&mut data)?;
+        data.pop();
+        records.push(Record {
+            at: Duration::from_micros(at),
+            channel: channel.to_owned(),
+            data,
+        });
+    }
+    Ok(records)
+}
+
+impl fmt::Display for Record {
+    /// One line per line of data, prefixed with the time and the channel
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let data = String::from_utf8_lossy(&self.data);
+        for line in data.lines() {
+            writeln!(
+                f,
+                "[{:>10.3}ms] {:<20} | {}",
+                self.at.as_secs_f64() * 1000.0,
+                self.channel,
+                line
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{Read, Write},
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{archive_path, read, remove_expired, Transcript};
+    use crate::{fifo::Fifo, game_dir::GameDir};
+
+    #[test]
+    fn traffic_is_recorded_and_passed_on() {
+        let path = std::env::temp_dir().join("cc-driver-transcript-test.gz");
+        let transcript = Transcript::create(&path).unwrap();
+
+        let mut fifo = Fifo::new("/tmp/cc-driver-transcript-fifo".to_owned()).unwrap();
+        let (fifo_in, mut fifo_out) = fifo.get_ends().unwrap();
+        let mut player_in = transcript
+            .intercept_input("simulator>player", fifo_in)
+            .unwrap();
+        let copying = Arc::downgrade(&transcript);
+        drop(transcript);
+
+        fifo_out.write_all(b"10 20\n").unwrap();
+        fifo_out.write_all(b"\xff binary\n").unwrap();
+        drop(fifo_out);
+        let mut received = vec![];
+        player_in.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"10 20\n\xff binary\n");
+
+        // the copying thread holds the last reference, the file is done once it's dropped
+        let mut records = read(&path);
+        for _ in 0..100 {
+            if copying.upgrade().is_none() && records.as_ref().is_ok_and(|r| !r.is_empty()) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+            records = read(&path);
+        }
+        let records = records.unwrap();
+        let data = records
+            .iter()
+            .flat_map(|record| record.data.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(data, b"10 20\n\xff binary\n");
+        assert!(records
+            .iter()
+            .all(|record| record.channel == "simulator>player"));
+        assert!(records[0].to_string().contains("simulator>player"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn transcript_outlives_the_game_dir() {
+        let root = std::env::temp_dir();
+        let archive = root.join("cc-driver-transcript-archive");
+        let mut game_dir =
+            GameDir::new(root.to_str().unwrap(), "archived", Duration::ZERO).unwrap();
+        let path = archive_path(archive.to_str().unwrap(), game_dir.get_path());
+        assert!(!path.starts_with(game_dir.get_path()));
+
+        std::fs::create_dir_all(&archive).unwrap();
+        let transcript = Transcript::create(&path).unwrap();
+        transcript.record("simulator>player", b"1 2\n").unwrap();
+        drop(transcript);
+        let played_in = game_dir.get_path().to_owned();
+        game_dir.succeeded();
+        drop(game_dir);
+
+        assert!(!std::path::Path::new(&played_in).exists());
+        assert_eq!(read(&path).unwrap()[0].data, b"1 2\n");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn old_transcripts_are_removed() {
+        let dir = std::env::temp_dir().join("cc-driver-transcript-retention");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = dir.join("old.gz");
+        let recent = dir.join("recent.gz");
+        let other = dir.join("notes.txt");
+        for path in [&old, &recent, &other] {
+            std::fs::write(path, "").unwrap();
+        }
+        let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+        for path in [&old, &other] {
+            File::options()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_modified(two_hours_ago))
+                .unwrap();
+        }
+
+        remove_expired(&dir, Duration::from_secs(60 * 60));
+        assert!(!old.exists());
+        assert!(recent.exists());
+        assert!(other.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,23 +1,24 @@
-use std::{
-    fs::File,
-    io::{BufWriter, Write},
-};
+use std::{collections::BTreeMap, io::Write, path::Path};
 
 use fs_extra::dir::CopyOptions;
 
-use crate::{create_error_response, error, request::GameRequest, response};
+use crate::{
+    error::SimulatorError,
+    runner::LanguageRunner,
+    validation::{check_file_path, check_reserved_path},
+};
 
 pub fn copy_dir_all(
     src: impl AsRef<std::path::Path>,
     dst: impl AsRef<std::path::Path>,
 ) -> std::io::Result<()> {
     let opt = CopyOptions::new();
-    for entry in std::fs::read_dir(src).unwrap() {
+    for entry in std::fs::read_dir(src)? {
         let entry = entry?;
         let ty = entry.file_type()?;
         if ty.is_dir() {
             fs_extra::dir::copy(entry.path(), dst.as_ref(), &opt)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e)))?;
+                .map_err(|e| std::io::Error::other(format!("{}", e)))?;
         } else {
             std::fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
         }
@@ -26,85 +27,53 @@ pub fn copy_dir_all(
     Ok(())
 }
 
-pub fn send_initial_input(fifos: Vec<&File>, game_request: &GameRequest) {
-    let game_parameters = &game_request.parameters;
-    for fifo in fifos {
-        let mut writer = BufWriter::new(fifo);
-        writer
-            .write_all(
-                format!(
-                    "{} {}\n",
-                    game_parameters.no_of_turns, game_parameters.no_of_coins
-                )
-                .as_bytes(),
-            )
-            .unwrap();
-        writer
-            .write_all(format!("{}\n", game_parameters.attackers.len()).as_bytes())
-            .unwrap();
-        for attacker in &game_parameters.attackers {
-            writer
-                .write_all(
-                    format!(
-                        "{} {} {} {} {}\n",
-                        attacker.hp,
-                        attacker.range,
-                        attacker.attack_power,
-                        attacker.speed,
-                        attacker.price
-                    )
-                    .as_bytes(),
-                )
-                .unwrap();
-        }
-        writer
-            .write_all(format!("{}\n", game_parameters.defenders.len()).as_bytes())
-            .unwrap();
-        for defender in &game_parameters.defenders {
-            writer
-                .write_all(
-                    format!(
-                        "{} {} {} {} {}\n",
-                        defender.hp, defender.range, defender.attack_power, 0, defender.price
-                    )
-                    .as_bytes(),
-                )
-                .unwrap();
-        }
-        writer.write_all("64 64\n".as_bytes()).unwrap();
-        for row in game_request.map.iter() {
-            for cell in row.iter() {
-                writer.write_all(format!("{} ", cell).as_bytes()).unwrap();
-            }
-            writer.write_all("\n".as_bytes()).unwrap();
-        }
-    }
-}
-
+/// Copies the language's boilerplate into `dest_dir` and writes the player's code next to
+/// it, either `source_code` as the language's source file or every submitted file.
+///
+/// Returns the submitted files besides the source file.
 pub fn make_copy(
-    src_dir: &str,
+    runner: &dyn LanguageRunner,
     dest_dir: &str,
-    player_code_file: &str,
-    game_request: &GameRequest,
-) -> Option<response::GameStatus> {
-    if let Err(e) = copy_dir_all(src_dir, dest_dir) {
-        return Some(create_error_response(
-            game_request,
-            error::SimulatorError::UnidentifiedError(format!(
-                "Failed to copy player code boilerplate: {}",
-                e
-            )),
-        ));
+    source_code: &str,
+    files: Option<&BTreeMap<String, String>>,
+) -> Result<Vec<String>, SimulatorError> {
+    copy_dir_all(runner.boilerplate_dir(), dest_dir).map_err(|e| {
+        SimulatorError::UnidentifiedError(format!("Failed to copy player code boilerplate: {}", e))
+    })?;
+
+    let files = match files {
+        Some(files) => files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_str()))
+            .collect(),
+        None => vec![(runner.source_file(), source_code)],
+    };
+    for (path, contents) in &files {
+        // already validated, checked again since the paths come from the player
+        check_file_path(path)
+            .and_then(|_| check_reserved_path(path, runner))
+            .map_err(|problem| {
+                SimulatorError::ValidationError(format!("File {:?} {}", path, problem))
+            })?;
+        write_player_file(&Path::new(dest_dir).join(path), contents).map_err(|e| {
+            SimulatorError::UnidentifiedError(format!("Failed to copy player code: {}", e))
+        })?;
     }
 
-    if let Err(e) = std::fs::File::create(player_code_file).and_then(|mut file| {
-        file.write_all(game_request.source_code.as_bytes())
-            .and_then(|_| file.sync_all())
-    }) {
-        return Some(create_error_response(
-            game_request,
-            error::SimulatorError::UnidentifiedError(format!("Failed to copy player code: {}", e)),
-        ));
+    Ok(files
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|&path| path != runner.source_file())
+        .map(|path| path.to_owned())
+        .collect())
+}
+
+/// Creates the file's directories as well, since files can be nested like src/run.rs
+fn write_player_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
     }
-    None
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()
 }
@@ -0,0 +1,595 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    config::{LimitOverrides, ValidationConfig},
+    error::SimulatorError,
+    request::{DriverRequest, GameParameters, Language},
+    runner::{LanguageRunner, RunnerRegistry},
+    simulator::PVP_FIFOS,
+};
+
+/// Checks the request before anything is run for it, the error lists every problem found
+pub fn validate(
+    request: &DriverRequest,
+    config: &ValidationConfig,
+    runners: &RunnerRegistry,
+) -> Result<(), SimulatorError> {
+    let mut problems = vec![];
+    match request {
+        DriverRequest::Normal(request) => {
+            check_parameters(&request.parameters, config, &mut problems);
+            check_limits(&request.limits, &mut problems);
+            check_code(
+                "code",
+                &request.source_code,
+                request.files.as_ref(),
+                request.language,
+                runners,
+                config,
+                &mut problems,
+            );
+            check_map(
+                "map",
+                &request.map,
+                &request.parameters,
+                config,
+                &mut problems,
+            );
+        }
+        DriverRequest::PvP(request) => {
+            check_parameters(&request.parameters, config, &mut problems);
+            check_limits(&request.limits, &mut problems);
+            for (i, player) in [&request.player1, &request.player2].iter().enumerate() {
+                check_code(
+                    &format!("player{} code", i + 1),
+                    &player.source_code,
+                    player.files.as_ref(),
+                    player.language,
+                    runners,
+                    config,
+                    &mut problems,
+                );
+                let name = format!("player{} map", i + 1);
+                check_map(
+                    &name,
+                    &player.map,
+                    &request.parameters,
+                    config,
+                    &mut problems,
+                );
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(SimulatorError::ValidationError(problems.join("\n")))
+    }
+}
+
+fn check_parameters(
+    parameters: &GameParameters,
+    config: &ValidationConfig,
+    problems: &mut Vec<String>,
+) {
+    if !(1..=config.max_turns).contains(&parameters.no_of_turns) {
+        problems.push(format!(
+            "no_of_turns is {}, it must be between 1 and {}",
+            parameters.no_of_turns, config.max_turns
+        ));
+    }
+    if !(1..=config.max_coins).contains(&parameters.no_of_coins) {
+        problems.push(format!(
+            "no_of_coins is {}, it must be between 1 and {}",
+            parameters.no_of_coins, config.max_coins
+        ));
+    }
+
+    let attackers = parameters
+        .attackers
+        .iter()
+        .map(|attacker| (attacker.id, attacker.hp))
+        .collect::<Vec<_>>();
+    let defenders = parameters
+        .defenders
+        .iter()
+        .map(|defender| (defender.id, defender.hp))
+        .collect::<Vec<_>>();
+    for (kind, troops) in [("attacker", attackers), ("defender", defenders)] {
+        let mut ids = HashSet::new();
+        for (id, hp) in troops {
+            // 0 marks the empty cells of the map
+            if id == 0 {
+                problems.push(format!("{} id 0 is reserved", kind));
+            } else if !ids.insert(id) {
+                problems.push(format!("{} id {} is used more than once", kind, id));
+            }
+            if hp == 0 {
+                problems.push(format!("{} {} has no hp", kind, id));
+            }
+        }
+    }
+}
+
+fn check_limits(limits: &LimitOverrides, problems: &mut Vec<String>) {
+    // a time limit of 0 disables the timeout
+    let names = [
+        "compilation_time",
+        "compilation_memory",
+        "runtime_time",
+        "runtime_memory",
+        "simulator_time",
+        "simulator_memory",
+    ];
+    for (name, value) in names.iter().zip(limits.values()) {
+        if value == Some(0) {
+            problems.push(format!("limits.{} must be at least 1", name));
+        }
+    }
+}
+
+/// Submitted paths have to stay inside the player's directory, docker can't mount paths
+/// containing `:` or `,`
+pub fn check_file_path(path: &str) -> Result<(), String> {
+    if path.is_empty() {
+        return Err("is empty".to_owned());
+    }
+    if path.contains(&[':', ',', '\0'][..]) {
+        return Err("can't contain ':', ',' or null bytes".to_owned());
+    }
+    if !Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err("has to be relative, without . or .. components".to_owned());
+    }
+    Ok(())
+}
+
+/// Submitted files can't replace or be replaced by what the driver puts next to them, the
+/// fifos, the language's boilerplate and its compiled artifacts
+pub fn check_reserved_path(path: &str, runner: &dyn LanguageRunner) -> Result<(), String> {
+    let path = Path::new(path);
+    if path == Path::new(runner.source_file()) {
+        return Ok(());
+    }
+    let mut reserved = PVP_FIFOS
+        .iter()
+        .chain(&runner.artifacts())
+        .chain(&runner.native_artifacts())
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    boilerplate_files(
+        Path::new(runner.boilerplate_dir()),
+        Path::new(""),
+        &mut reserved,
+    );
+    match reserved
+        .iter()
+        .filter(|reserved| *reserved != Path::new(runner.source_file()))
+        .find(|reserved| path.starts_with(reserved) || reserved.starts_with(path))
+    {
+        Some(reserved) => Err(format!(
+            "collides with {:?}, which the driver creates",
+            reserved
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Sources the compiler wouldn't pick up, in a subdirectory
+fn check_source_path(path: &str, runner: &dyn LanguageRunner) -> Result<(), String> {
+    let path = Path::new(path);
+    match runner.flat_source_extension() {
+        Some(extension)
+            if path.extension() == Some(extension.as_ref())
+                && path.parent() != Some(Path::new("")) =>
+        {
+            Err(format!(
+                "is in a subdirectory, only the .{} files next to {:?} are compiled",
+                extension,
+                runner.source_file()
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Every file in the boilerplate, relative to its root. Missing directories are skipped.
+fn boilerplate_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = relative.join(entry.file_name());
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => boilerplate_files(&entry.path(), &path, files),
+            _ => files.push(path),
+        }
+    }
+}
+
+fn check_code(
+    name: &str,
+    source_code: &str,
+    files: Option<&BTreeMap<String, String>>,
+    language: Language,
+    runners: &RunnerRegistry,
+    config: &ValidationConfig,
+    problems: &mut Vec<String>,
+) {
+    let files = match files {
+        Some(_) if !source_code.is_empty() => {
+            problems.push(format!(
+                "{} has both source_code and files, only one of them can be given",
+                name
+            ));
+            return;
+        }
+        Some(files) => files,
+        None => {
+            if source_code.len() > config.max_file_size {
+                problems.push(format!(
+                    "{} is {} bytes, it can't be larger than {}",
+                    name,
+                    source_code.len(),
+                    config.max_file_size
+                ));
+            }
+            return;
+        }
+    };
+
+    if files.len() > config.max_files {
+        problems.push(format!(
+            "{} has {} files, at most {} can be submitted",
+            name,
+            files.len(),
+            config.max_files
+        ));
+    }
+    let runner = runners.get(&language);
+    for (path, contents) in files {
+        let checked = check_file_path(path).and_then(|_| {
+            runner.map_or(Ok(()), |runner| {
+                check_reserved_path(path, runner).and_then(|_| check_source_path(path, runner))
+            })
+        });
+        if let Err(problem) = checked {
+            problems.push(format!("{} file {:?} {}", name, path, problem));
+        }
+        if contents.len() > config.max_file_size {
+            problems.push(format!(
+                "{} file {:?} is {} bytes, it can't be larger than {}",
+                name,
+                path,
+                contents.len(),
+                config.max_file_size
+            ));
+        }
+    }
+    let size = files.values().map(|contents| contents.len()).sum::<usize>();
+    if size > config.max_submission_size {
+        problems.push(format!(
+            "{} is {} bytes, it can't be larger than {}",
+            name, size, config.max_submission_size
+        ));
+    }
+    if let Some(runner) = runner {
+        if !files.contains_key(runner.source_file()) {
+            problems.push(format!(
+                "{} has no {:?} file, which the {} boilerplate runs",
+                name,
+                runner.source_file(),
+                runner.name()
+            ));
+        }
+    }
+}
+
+fn check_map(
+    name: &str,
+    map: &[Vec<u8>],
+    parameters: &GameParameters,
+    config: &ValidationConfig,
+    problems: &mut Vec<String>,
+) {
+    let columns = map.first().map(|row| row.len()).unwrap_or(0);
+    if map.is_empty() || columns == 0 {
+        problems.push(format!("{} is empty", name));
+    }
+    if map.len() > config.max_map_size || columns > config.max_map_size {
+        problems.push(format!(
+            "{} is {}x{}, it can't be larger than {}x{}",
+            name,
+            map.len(),
+            columns,
+            config.max_map_size,
+            config.max_map_size
+        ));
+    }
+    // the dimensions sent to the players are taken from the first row
+    let uneven_rows = map
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| row.len() != columns)
+        .map(|(i, row)| format!("{} ({} cells)", i, row.len()))
+        .collect::<Vec<_>>();
+    if !uneven_rows.is_empty() {
+        problems.push(format!(
+            "{} rows {} must have {} cells like the first row",
+            name,
+            uneven_rows.join(", "),
+            columns
+        ));
+    }
+
+    // grouped by value, a bad map would otherwise produce thousands of problems
+    let defenders = parameters
+        .defenders
+        .iter()
+        .map(|defender| defender.id)
+        .collect::<HashSet<u32>>();
+    let mut illegal = BTreeMap::new();
+    for (i, row) in map.iter().enumerate() {
+        for (j, &cell) in row.iter().enumerate() {
+            if cell != 0 && !defenders.contains(&(cell as u32)) {
+                illegal.entry(cell).or_insert((i, j, 0)).2 += 1;
+            }
+        }
+    }
+    for (cell, (i, j, count)) in illegal {
+        let cells = match count {
+            1 => format!("cell ({}, {}) holds", i, j),
+            count => format!("cells ({}, {}) and {} more hold", i, j, count - 1),
+        };
+        problems.push(format!(
+            "{} {} {}, which is neither 0 nor a defender id",
+            name, cells, cell
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::validate;
+    use crate::{
+        config::{LimitOverrides, ValidationConfig},
+        error::SimulatorError,
+        request::{Attacker, Defender, DriverRequest, GameParameters, GameRequest, Language},
+        runner::RunnerRegistry,
+    };
+
+    fn request(map: Vec<Vec<u8>>, parameters: GameParameters) -> DriverRequest {
+        request_with_limits(map, parameters, LimitOverrides::default())
+    }
+
+    fn request_with_limits(
+        map: Vec<Vec<u8>>,
+        parameters: GameParameters,
+        limits: LimitOverrides,
+    ) -> DriverRequest {
+        DriverRequest::Normal(GameRequest {
+            game_id: "validation".to_owned(),
+            parameters,
+            source_code: String::new(),
+            files: None,
+            language: Language::CPP,
+            map,
+            log_format: None,
+            limits,
+        })
+    }
+
+    fn parameters() -> GameParameters {
+        GameParameters {
+            attackers: vec![Attacker {
+                id: 1,
+                hp: 10,
+                range: 3,
+                attack_power: 3,
+                speed: 3,
+                price: 1,
+            }],
+            defenders: vec![Defender {
+                id: 1,
+                hp: 10,
+                range: 4,
+                attack_power: 5,
+                price: 1,
+            }],
+            no_of_turns: 500,
+            no_of_coins: 1000,
+        }
+    }
+
+    #[test]
+    fn valid_request_passes() {
+        let mut map = vec![vec![0; 64]; 64];
+        map[3][4] = 1;
+        assert!(validate(
+            &request(map, parameters()),
+            &ValidationConfig::default(),
+            &RunnerRegistry::default()
+        )
+        .is_ok());
+
+        let mut map = vec![vec![0; 20]; 10];
+        map[9][19] = 1;
+        assert!(validate(
+            &request(map, parameters()),
+            &ValidationConfig::default(),
+            &RunnerRegistry::default()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn every_problem_is_listed() {
+        let problems = validate(
+            &request(vec![], parameters()),
+            &ValidationConfig::default(),
+            &RunnerRegistry::default(),
+        );
+        assert!(matches!(problems, Err(SimulatorError::ValidationError(p)) if p == "map is empty"));
+
+        let mut parameters = parameters();
+        parameters.no_of_turns = 0;
+        parameters.no_of_coins = 1_000_000;
+        parameters.attackers.push(Attacker {
+            id: 1,
+            hp: 0,
+            range: 3,
+            attack_power: 3,
+            speed: 3,
+            price: 1,
+        });
+        parameters.defenders[0].id = 0;
+
+        let mut map = vec![vec![0; 65]; 63];
+        map[1].pop();
+        map[4].push(0);
+        map[2][5] = 7;
+        map[9][9] = 7;
+        map[3][3] = 2;
+
+        let limits = LimitOverrides {
+            runtime_time: Some(0),
+            runtime_memory: Some(200),
+            ..Default::default()
+        };
+        let request = request_with_limits(map, parameters, limits);
+        let problems = match validate(
+            &request,
+            &ValidationConfig::default(),
+            &RunnerRegistry::default(),
+        ) {
+            Err(SimulatorError::ValidationError(problems)) => problems,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            problems.lines().collect::<Vec<_>>(),
+            vec![
+                "no_of_turns is 0, it must be between 1 and 2000",
+                "no_of_coins is 1000000, it must be between 1 and 100000",
+                "attacker id 1 is used more than once",
+                "attacker 1 has no hp",
+                "defender id 0 is reserved",
+                "limits.runtime_time must be at least 1",
+                "map is 63x65, it can't be larger than 64x64",
+                "map rows 1 (64 cells), 4 (66 cells) must have 65 cells like the first row",
+                "map cell (3, 3) holds 2, which is neither 0 nor a defender id",
+                "map cells (2, 5) and 1 more hold 7, which is neither 0 nor a defender id",
+            ]
+        );
+    }
+
+    #[test]
+    fn submitted_files_are_checked() {
+        let files = |files: &[(&str, usize)]| {
+            files
+                .iter()
+                .map(|(path, size)| (path.to_string(), "x".repeat(*size)))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let validate_files = |source_code: &str, files: BTreeMap<String, String>| {
+            let mut map = vec![vec![0; 4]; 4];
+            map[0][0] = 1;
+            let mut request = match request(map, parameters()) {
+                DriverRequest::Normal(request) => request,
+                _ => unreachable!(),
+            };
+            request.source_code = source_code.to_owned();
+            request.files = Some(files);
+            let config = ValidationConfig {
+                max_files: 3,
+                max_file_size: 100,
+                max_submission_size: 150,
+                ..Default::default()
+            };
+            validate(
+                &DriverRequest::Normal(request),
+                &config,
+                &RunnerRegistry::default(),
+            )
+        };
+
+        assert!(validate_files("", files(&[("run.cpp", 100), ("lib/bot.hpp", 50)])).is_ok());
+
+        // g++ only compiles the sources next to run.cpp
+        let problems = match validate_files("", files(&[("run.cpp", 1), ("lib/bot.cpp", 1)])) {
+            Err(SimulatorError::ValidationError(problems)) => problems,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            problems,
+            r#"code file "lib/bot.cpp" is in a subdirectory, only the .cpp files next to "run.cpp" are compiled"#
+        );
+
+        // the fifos and the compiled player would be overwritten
+        let problems = match validate_files("", files(&[("run.cpp", 1), ("p1_in", 1)])) {
+            Err(SimulatorError::ValidationError(problems)) => problems,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            problems,
+            r#"code file "p1_in" collides with "p1_in", which the driver creates"#
+        );
+        let problems = match validate_files(
+            "",
+            files(&[("run.cpp", 1), ("run/bot.hpp", 1), ("player", 1)]),
+        ) {
+            Err(SimulatorError::ValidationError(problems)) => problems,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            problems.lines().collect::<Vec<_>>(),
+            vec![
+                r#"code file "player" collides with "player", which the driver creates"#,
+                r#"code file "run/bot.hpp" collides with "run", which the driver creates"#,
+            ]
+        );
+
+        let problems = match validate_files("int main() {}", files(&[("run.cpp", 1)])) {
+            Err(SimulatorError::ValidationError(problems)) => problems,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            problems,
+            "code has both source_code and files, only one of them can be given"
+        );
+
+        let problems = match validate_files(
+            "",
+            files(&[
+                ("../escape.cpp", 1),
+                ("/etc/passwd", 1),
+                ("./bot.cpp", 101),
+                ("a:b.cpp", 60),
+            ]),
+        ) {
+            Err(SimulatorError::ValidationError(problems)) => problems,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            problems.lines().collect::<Vec<_>>(),
+            vec![
+                "code has 4 files, at most 3 can be submitted",
+                r#"code file "../escape.cpp" has to be relative, without . or .. components"#,
+                r#"code file "./bot.cpp" has to be relative, without . or .. components"#,
+                r#"code file "./bot.cpp" is 101 bytes, it can't be larger than 100"#,
+                r#"code file "/etc/passwd" has to be relative, without . or .. components"#,
+                r#"code file "a:b.cpp" can't contain ':', ',' or null bytes"#,
+                "code is 163 bytes, it can't be larger than 150",
+                r#"code has no "run.cpp" file, which the C++ boilerplate runs"#,
+            ]
+        );
+    }
+}